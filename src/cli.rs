@@ -0,0 +1,172 @@
+use crate::{config::ClockBackend, transport::MAX_SWING};
+use clap::Parser;
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// A live-coding grid for making music
+#[derive(Parser, Debug)]
+#[command(name = "snorkel", version)]
+pub struct Args {
+    /// `.orca` file to open, created on first save if it does not exist
+    pub file: Option<PathBuf>,
+
+    /// Grid size as COLSxROWS, defaults to the terminal size
+    #[arg(long)]
+    pub size: Option<Size>,
+
+    /// Tempo in beats per minute
+    #[arg(long, default_value_t = 120, value_parser = clap::value_parser!(u16).range(1..))]
+    pub bpm: u16,
+
+    /// Delay every other frame by this many percent of a frame
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=MAX_SWING as i64))]
+    pub swing: u8,
+
+    /// Name (or part of the name) or id of the MIDI output device
+    #[arg(long)]
+    pub midi_device: Option<String>,
+
+    /// Send MIDI clock so external gear follows the tempo
+    #[arg(long)]
+    pub midi_clock: bool,
+
+    /// How to wait between frames
+    #[arg(long, value_enum, default_value_t)]
+    pub clock: ClockBackend,
+
+    /// Name (or part of the name) of the MIDI input device to follow with
+    /// `--clock midi`
+    #[arg(long)]
+    pub midi_input: Option<String>,
+
+    /// Where `=` sends OSC messages to, as HOST:PORT
+    #[arg(long, value_name = "HOST:PORT", value_parser = target)]
+    pub osc: Option<SocketAddr>,
+
+    /// Where `;` sends UDP messages to, as HOST:PORT
+    #[arg(long, value_name = "HOST:PORT", value_parser = target)]
+    pub udp: Option<SocketAddr>,
+
+    /// Run the grid without the editor
+    #[arg(long)]
+    pub headless: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Size {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl Default for Size {
+    fn default() -> Self {
+        Self { cols: 64, rows: 32 }
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (cols, rows) = s
+            .split_once('x')
+            .ok_or_else(|| format!("expected COLSxROWS, got '{}'", s))?;
+        let parse = |n: &str| match n.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("invalid grid dimension '{}'", n)),
+            Ok(n) => Ok(n),
+        };
+        Ok(Size {
+            cols: parse(cols)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+/// Resolves a HOST:PORT target, taking the first address the host has.
+fn target(s: &str) -> Result<SocketAddr, String> {
+    s.to_socket_addrs()
+        .map_err(|err| format!("invalid target '{}': {}", s, err))?
+        .next()
+        .ok_or_else(|| format!("no address found for '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Args, Size};
+    use crate::config::ClockBackend;
+    use clap::Parser;
+    use std::{net::SocketAddr, path::PathBuf};
+
+    #[test]
+    fn should_use_defaults() {
+        let args = Args::try_parse_from(["snorkel"]).unwrap();
+        assert_eq!(None, args.file);
+        assert_eq!(None, args.size);
+        assert_eq!(120, args.bpm);
+        assert_eq!(None, args.midi_device);
+        assert!(!args.midi_clock);
+        assert_eq!(None, args.osc);
+        assert_eq!(None, args.udp);
+        assert!(!args.headless);
+    }
+
+    #[test]
+    fn should_parse_all_options() {
+        let args = Args::try_parse_from([
+            "snorkel",
+            "song.orca",
+            "--size",
+            "64x32",
+            "--bpm",
+            "90",
+            "--swing",
+            "20",
+            "--midi-device",
+            "Digitakt",
+            "--midi-clock",
+            "--clock",
+            "timerfd",
+            "--midi-input",
+            "DAW",
+            "--osc",
+            "127.0.0.1:9000",
+            "--udp",
+            "localhost:9001",
+            "--headless",
+        ])
+        .unwrap();
+        assert_eq!(Some(PathBuf::from("song.orca")), args.file);
+        assert_eq!(Some(Size { cols: 64, rows: 32 }), args.size);
+        assert_eq!(90, args.bpm);
+        assert_eq!(20, args.swing);
+        assert_eq!(Some("Digitakt".to_string()), args.midi_device);
+        assert!(args.midi_clock);
+        assert_eq!(ClockBackend::TimerFd, args.clock);
+        assert_eq!(Some("DAW".to_string()), args.midi_input);
+        assert_eq!(Some(SocketAddr::from(([127, 0, 0, 1], 9000))), args.osc);
+        assert_eq!(Some(9001), args.udp.map(|addr| addr.port()));
+        assert!(args.headless);
+    }
+
+    #[test]
+    fn should_reject_bad_size() {
+        assert!("64".parse::<Size>().is_err());
+        assert!("0x32".parse::<Size>().is_err());
+        assert!("64xa".parse::<Size>().is_err());
+        assert!(Args::try_parse_from(["snorkel", "--size", "big"]).is_err());
+    }
+
+    #[test]
+    fn should_reject_bad_targets() {
+        assert!(Args::try_parse_from(["snorkel", "--osc", "9000"]).is_err());
+        assert!(Args::try_parse_from(["snorkel", "--udp", "localhost"]).is_err());
+    }
+
+    #[test]
+    fn should_reject_zero_bpm() {
+        assert!(Args::try_parse_from(["snorkel", "--bpm", "0"]).is_err());
+    }
+}
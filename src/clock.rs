@@ -0,0 +1,531 @@
+use crate::{
+    config::ClockBackend,
+    output::{Listener, Packet},
+};
+use ::timerfd::{SetTimeFlags, TimerFd, TimerState};
+use portmidi as pm;
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// What happened while waiting on a clock.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Tick {
+    /// It is time for the next tick.
+    Now,
+    /// Nothing happened yet, but the clock is still running.
+    Idle,
+    /// The clock will not tick anymore.
+    Done,
+}
+
+/// Transport changes requested by an external clock.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Sync {
+    Start,
+    Stop,
+    Continue,
+    /// Song position in 16th notes, which are frames.
+    Position(usize),
+}
+
+/// Something that can wait for the next tick.
+pub trait Clock: Send {
+    /// Blocks until `interval` after the previous tick.
+    fn wait(&mut self, interval: Duration) -> Tick;
+
+    /// Whether the thread waiting on this clock should get real time
+    /// priority.
+    fn realtime(&self) -> bool {
+        true
+    }
+
+    /// Whether every tick is a MIDI clock pulse, no matter the interval
+    /// that was asked for.
+    fn pulses(&self) -> bool {
+        false
+    }
+
+    /// The tempo the clock is running at, if it is set from outside.
+    fn bpm(&self) -> Option<f64> {
+        None
+    }
+
+    /// The next transport change received since the last call.
+    fn sync(&mut self) -> Option<Sync> {
+        None
+    }
+}
+
+/// Opens the clock for `backend`, falling back to spin sleeping if it is not
+/// available. A MIDI clock is read by the output thread behind `output`.
+pub fn open(
+    backend: ClockBackend,
+    midi_input: Option<&str>,
+    output: &Sender<Packet>,
+) -> Box<dyn Clock> {
+    match backend {
+        ClockBackend::SpinSleep => Box::new(SpinSleepClock::new()),
+        ClockBackend::TimerFd => match TimerFdClock::new() {
+            Ok(clock) => Box::new(clock),
+            Err(err) => {
+                log::warn!("could not create timerfd, spin sleeping instead: {}", err);
+                Box::new(SpinSleepClock::new())
+            }
+        },
+        ClockBackend::Midi => match MidiClock::open(midi_input.map(String::from), output) {
+            Ok(clock) => Box::new(clock),
+            Err(err) => {
+                log::warn!("could not open midi input, spin sleeping instead: {}", err);
+                Box::new(SpinSleepClock::new())
+            }
+        },
+    }
+}
+
+// ░█▀▀░█▀█░▀█▀░█▀█
+// ░▀▀█░█▀▀░░█░░█░█
+// ░▀▀▀░▀░░░▀▀▀░▀░▀
+
+/// Sleeps until each deadline using `spin_sleep`, spinning for the last
+/// 100us to make up for the scheduler's granularity.
+pub struct SpinSleepClock {
+    sleeper: spin_sleep::SpinSleeper,
+    next: Instant,
+}
+
+impl SpinSleepClock {
+    pub fn new() -> Self {
+        Self {
+            sleeper: spin_sleep::SpinSleeper::new(100_000),
+            next: Instant::now(),
+        }
+    }
+}
+
+impl Default for SpinSleepClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SpinSleepClock {
+    fn wait(&mut self, interval: Duration) -> Tick {
+        // sleep until the next deadline rather than for `interval`, so the
+        // time spent between waits does not add up to drift
+        self.next += interval;
+        let now = Instant::now();
+        if self.next > now {
+            self.sleeper.sleep(self.next - now);
+        } else {
+            // we fell behind, skip ahead instead of rushing through ticks
+            self.next = now;
+        }
+        Tick::Now
+    }
+}
+
+// ░▀█▀░▀█▀░█▄█░█▀▀░█▀▄░█▀▀░█▀▄
+// ░░█░░░█░░█░█░█▀▀░█▀▄░█▀▀░█░█
+// ░░▀░░▀▀▀░▀░▀░▀▀▀░▀░▀░▀░░░▀▀░
+
+/// Waits on a kernel timer armed for absolute deadlines, so neither the
+/// time spent between waits nor changing intervals add up to drift.
+pub struct TimerFdClock {
+    tfd: TimerFd,
+    /// The last deadline, on the monotonic clock the timer runs on.
+    next: Option<Duration>,
+}
+
+impl TimerFdClock {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            tfd: TimerFd::new()?,
+            next: None,
+        })
+    }
+
+    /// Moves on to the deadline `interval` after the last one, which is
+    /// returned unless it already passed at `now`.
+    fn advance(&mut self, now: Duration, interval: Duration) -> Option<Duration> {
+        let next = self.next.unwrap_or(now) + interval;
+        if next > now {
+            self.next = Some(next);
+            Some(next)
+        } else {
+            // we fell behind, skip ahead instead of rushing through ticks
+            self.next = Some(now);
+            None
+        }
+    }
+}
+
+impl Clock for TimerFdClock {
+    fn wait(&mut self, interval: Duration) -> Tick {
+        if let Some(next) = self.advance(monotonic_now(), interval) {
+            self.tfd
+                .set_state(TimerState::Oneshot(next), SetTimeFlags::Abstime);
+            self.tfd.read();
+        }
+        Tick::Now
+    }
+}
+
+/// The time on `CLOCK_MONOTONIC`, which timerfd deadlines refer to.
+fn monotonic_now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+// ░█░█░▀█▀░█▀▄░▀█▀░█░█░█▀█░█░░
+// ░▀▄▀░░█░░█▀▄░░█░░█░█░█▀█░█░░
+// ░░▀░░▀▀▀░▀░▀░░▀░░▀▀▀░▀░▀░▀▀▀
+
+/// A clock that only ticks when told to through its [`VirtualHandle`], for
+/// running the engine deterministically.
+pub struct VirtualClock {
+    ticks: Receiver<()>,
+    intervals: Arc<Mutex<Vec<Duration>>>,
+}
+
+pub struct VirtualHandle {
+    ticks: SyncSender<()>,
+    intervals: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> (VirtualClock, VirtualHandle) {
+        // a rendezvous channel, so `advance` returns once every tick has
+        // been picked up
+        let (tx, rx) = mpsc::sync_channel(0);
+        let intervals = Arc::new(Mutex::new(vec![]));
+        let clock = VirtualClock {
+            ticks: rx,
+            intervals: intervals.clone(),
+        };
+        let handle = VirtualHandle {
+            ticks: tx,
+            intervals,
+        };
+        (clock, handle)
+    }
+}
+
+impl Clock for VirtualClock {
+    fn wait(&mut self, interval: Duration) -> Tick {
+        self.intervals.lock().unwrap().push(interval);
+        match self.ticks.recv() {
+            Ok(()) => Tick::Now,
+            Err(_) => Tick::Done,
+        }
+    }
+
+    fn realtime(&self) -> bool {
+        false
+    }
+}
+
+impl VirtualHandle {
+    /// Lets the clock tick `n` times. The clock stops when the handle is
+    /// dropped.
+    pub fn advance(&self, n: usize) {
+        for _ in 0..n {
+            if self.ticks.send(()).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// The intervals the clock has been asked to wait for so far.
+    pub fn intervals(&self) -> Vec<Duration> {
+        self.intervals.lock().unwrap().clone()
+    }
+}
+
+// ░█▄█░▀█▀░█▀▄░▀█▀
+// ░█░█░░█░░█░█░░█░
+// ░▀░▀░▀▀▀░▀▀░░▀▀▀
+
+/// Messages of the MIDI clock protocol.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ClockMsg {
+    Pulse,
+    Sync(Sync),
+}
+
+impl ClockMsg {
+    pub fn parse(msg: pm::MidiMessage) -> Option<ClockMsg> {
+        match msg.status {
+            0xF8 => Some(ClockMsg::Pulse),
+            0xFA => Some(ClockMsg::Sync(Sync::Start)),
+            0xFB => Some(ClockMsg::Sync(Sync::Continue)),
+            0xFC => Some(ClockMsg::Sync(Sync::Stop)),
+            0xF2 => {
+                let pos = ((msg.data2 as usize) << 7) | msg.data1 as usize;
+                Some(ClockMsg::Sync(Sync::Position(pos)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Turns the time between clock pulses into a smoothed tempo.
+#[derive(Debug, Default)]
+pub struct TempoTracker {
+    last: Option<Instant>,
+    period: Option<f64>,
+}
+
+impl TempoTracker {
+    /// How much a new pulse moves the average, lower is smoother.
+    const SMOOTHING: f64 = 0.1;
+
+    pub fn pulse(&mut self, at: Instant) {
+        if let Some(last) = self.last {
+            let dt = at.duration_since(last).as_secs_f64();
+            // a longer gap means the clock was stopped in between
+            if dt < 1.0 {
+                self.period = Some(match self.period {
+                    Some(period) => period + (dt - period) * Self::SMOOTHING,
+                    None => dt,
+                });
+            }
+        }
+        self.last = Some(at);
+    }
+
+    /// Forgets the last pulse, e.g. when the clock was restarted.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    pub fn bpm(&self) -> Option<f64> {
+        self.period
+            .filter(|period| *period > 0.0)
+            .map(|period| 60.0 / (period * 24.0))
+    }
+}
+
+/// Follows the MIDI clock of another device, ticking once per pulse.
+pub struct MidiClock {
+    rx: Receiver<(ClockMsg, Instant)>,
+    tempo: TempoTracker,
+    pending: VecDeque<Sync>,
+}
+
+impl MidiClock {
+    /// How long to wait for a pulse before handing control back, so the
+    /// transport can still be stopped when the clock goes quiet.
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Follows the messages sent through `rx`, stamped with their arrival.
+    pub fn new(rx: Receiver<(ClockMsg, Instant)>) -> MidiClock {
+        MidiClock {
+            rx,
+            tempo: TempoTracker::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Listens on the input device whose name contains `name`, or the
+    /// default input device when no name is given. The device is read by
+    /// the output thread behind `output`, on its PortMidi context.
+    pub fn open(name: Option<String>, output: &Sender<Packet>) -> pm::Result<MidiClock> {
+        let (tx, rx) = mpsc::channel();
+        let (opened_tx, opened_rx) = mpsc::channel();
+        output
+            .send(Packet::Listen(Listener {
+                device: name,
+                clock: tx,
+                opened: opened_tx,
+            }))
+            .map_err(|_| pm::Error::Unknown)?;
+        opened_rx.recv().unwrap_or(Err(pm::Error::Unknown))?;
+        Ok(MidiClock::new(rx))
+    }
+}
+
+impl Clock for MidiClock {
+    fn wait(&mut self, _interval: Duration) -> Tick {
+        loop {
+            match self.rx.recv_timeout(Self::TIMEOUT) {
+                Ok((ClockMsg::Pulse, at)) => {
+                    self.tempo.pulse(at);
+                    return Tick::Now;
+                }
+                Ok((ClockMsg::Sync(sync), _)) => {
+                    if sync != Sync::Continue {
+                        self.tempo.reset();
+                    }
+                    self.pending.push_back(sync);
+                }
+                Err(RecvTimeoutError::Timeout) => return Tick::Idle,
+                Err(RecvTimeoutError::Disconnected) => return Tick::Done,
+            }
+        }
+    }
+
+    /// Pulses are only seen when the output thread reads the input, so
+    /// waking up any sooner does not make them more punctual.
+    fn realtime(&self) -> bool {
+        false
+    }
+
+    fn pulses(&self) -> bool {
+        true
+    }
+
+    fn bpm(&self) -> Option<f64> {
+        self.tempo.bpm()
+    }
+
+    fn sync(&mut self) -> Option<Sync> {
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Clock, ClockMsg, MidiClock, SpinSleepClock, Sync, TempoTracker, Tick, TimerFdClock,
+        VirtualClock,
+    };
+    use portmidi::MidiMessage;
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn spin_sleep_clock_should_wait_for_deadlines() {
+        let mut clock = SpinSleepClock::new();
+        let start = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(Tick::Now, clock.wait(Duration::from_millis(2)));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timerfd_clock_should_wait_for_deadlines() {
+        let mut clock = TimerFdClock::new().unwrap();
+        let start = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(Tick::Now, clock.wait(Duration::from_millis(2)));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timerfd_clock_should_not_drift_with_changing_intervals() {
+        let mut clock = TimerFdClock::new().unwrap();
+        let start = Duration::from_secs(100);
+        let mut now = start;
+        let mut total = Duration::ZERO;
+        // swung frames alternate intervals, with some work in between
+        for i in 0..10 {
+            let interval = Duration::from_millis(if i % 2 == 0 { 10 } else { 20 });
+            total += interval;
+            let next = clock.advance(now, interval).unwrap();
+            // the deadline only depends on the first one and the intervals,
+            // not on when the clock was asked
+            assert_eq!(start + total, next);
+            now = next + Duration::from_millis(5);
+        }
+
+        // a clock that fell behind skips ahead
+        let now = start + total + Duration::from_millis(50);
+        assert_eq!(None, clock.advance(now, Duration::from_millis(10)));
+        assert_eq!(
+            Some(now + Duration::from_millis(10)),
+            clock.advance(now, Duration::from_millis(10))
+        );
+    }
+
+    #[test]
+    fn virtual_clock_should_tick_when_advanced() {
+        let (mut clock, handle) = VirtualClock::new();
+        let waiter = thread::spawn(move || {
+            let mut ticks = 0;
+            while clock.wait(Duration::from_millis(ticks + 1)) == Tick::Now {
+                ticks += 1;
+            }
+            ticks
+        });
+        handle.advance(3);
+        let intervals = handle.intervals();
+        drop(handle);
+        assert_eq!(3, waiter.join().unwrap());
+        assert_eq!(
+            vec![
+                Duration::from_millis(1),
+                Duration::from_millis(2),
+                Duration::from_millis(3)
+            ],
+            intervals[..3]
+        );
+    }
+
+    #[test]
+    fn should_parse_clock_messages() {
+        let msg = |status, data1, data2| {
+            ClockMsg::parse(MidiMessage {
+                status,
+                data1,
+                data2,
+                data3: 0,
+            })
+        };
+        assert_eq!(Some(ClockMsg::Pulse), msg(0xF8, 0, 0));
+        assert_eq!(Some(ClockMsg::Sync(Sync::Start)), msg(0xFA, 0, 0));
+        assert_eq!(Some(ClockMsg::Sync(Sync::Continue)), msg(0xFB, 0, 0));
+        assert_eq!(Some(ClockMsg::Sync(Sync::Stop)), msg(0xFC, 0, 0));
+        assert_eq!(
+            Some(ClockMsg::Sync(Sync::Position(130))),
+            msg(0xF2, 0x02, 0x01)
+        );
+        assert_eq!(None, msg(0x90, 60, 100));
+    }
+
+    #[test]
+    fn tempo_should_follow_pulses_smoothly() {
+        let mut tempo = TempoTracker::default();
+        let start = Instant::now();
+        // 120 bpm makes a pulse every 1/48s
+        let pulse = Duration::from_secs(1) / 48;
+        for i in 0..48 {
+            tempo.pulse(start + pulse * i);
+        }
+        assert!((tempo.bpm().unwrap() - 120.0).abs() < 0.01);
+
+        // a single late pulse only moves the tempo a little
+        tempo.pulse(start + pulse * 49);
+        let bpm = tempo.bpm().unwrap();
+        assert!(bpm < 120.0 && bpm > 100.0, "{}", bpm);
+    }
+
+    #[test]
+    fn midi_clock_should_tick_on_pulses() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut clock = MidiClock::new(rx);
+        let now = Instant::now();
+        tx.send((ClockMsg::Sync(Sync::Start), now)).unwrap();
+        tx.send((ClockMsg::Pulse, now)).unwrap();
+        assert_eq!(Tick::Now, clock.wait(Duration::ZERO));
+        assert_eq!(Some(Sync::Start), clock.sync());
+        assert_eq!(None, clock.sync());
+        assert_eq!(Tick::Idle, clock.wait(Duration::ZERO));
+        drop(tx);
+        assert_eq!(Tick::Done, clock.wait(Duration::ZERO));
+    }
+}
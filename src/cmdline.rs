@@ -0,0 +1,323 @@
+use crate::util::Coord;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, digit1, space0, space1},
+    combinator::{all_consuming, map, map_res, rest, value, verify},
+    sequence::{preceded, separated_pair, tuple},
+    IResult,
+};
+use std::{fs, path::PathBuf};
+
+/// Commands typed on the editor's `:` command line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EditorCommand {
+    /// Save the grid, to its current file when no path is given.
+    Write(Option<PathBuf>),
+    /// Load the grid from a file.
+    Edit(PathBuf),
+    Quit,
+    Resize {
+        cols: usize,
+        rows: usize,
+    },
+    Bpm(usize),
+    /// Spacing of the grid markers.
+    Grid {
+        x: usize,
+        y: usize,
+    },
+    Goto(Coord),
+}
+
+/// Names of all commands, for completion.
+const COMMANDS: [&str; 10] = [
+    "bpm", "e", "edit", "goto", "grid", "q", "quit", "resize", "w", "write",
+];
+
+/// Commands that take a file name.
+const FILE_COMMANDS: [&str; 4] = ["e", "edit", "w", "write"];
+
+impl EditorCommand {
+    /// Parses a command line such as `w song.orca` or `goto 10,4`.
+    pub fn parse(input: &str) -> Option<EditorCommand> {
+        all_consuming(command)(input.trim())
+            .ok()
+            .map(|(_, cmd)| cmd)
+    }
+}
+
+fn command(input: &str) -> IResult<&str, EditorCommand> {
+    alt((
+        map(
+            preceded(tuple((alt((tag("write"), tag("w"))), space1)), path),
+            |path| EditorCommand::Write(Some(path)),
+        ),
+        value(EditorCommand::Write(None), alt((tag("write"), tag("w")))),
+        map(
+            preceded(tuple((alt((tag("edit"), tag("e"))), space1)), path),
+            EditorCommand::Edit,
+        ),
+        value(EditorCommand::Quit, alt((tag("quit"), tag("q")))),
+        map(preceded(tag("resize"), pair), |(cols, rows)| {
+            EditorCommand::Resize { cols, rows }
+        }),
+        map(preceded(tag("grid"), pair), |(x, y)| EditorCommand::Grid {
+            x,
+            y,
+        }),
+        map(
+            preceded(tuple((tag("bpm"), space1)), number),
+            EditorCommand::Bpm,
+        ),
+        map(
+            preceded(
+                tuple((tag("goto"), space1)),
+                separated_pair(number, tuple((char(','), space0)), number),
+            ),
+            |(x, y)| EditorCommand::Goto(Coord { x, y }),
+        ),
+    ))(input)
+}
+
+/// Two numbers, each preceded by spaces.
+fn pair(input: &str) -> IResult<&str, (usize, usize)> {
+    tuple((preceded(space1, number), preceded(space1, number)))(input)
+}
+
+fn path(input: &str) -> IResult<&str, PathBuf> {
+    map(verify(rest, |s: &str| !s.is_empty()), PathBuf::from)(input)
+}
+
+fn number(input: &str) -> IResult<&str, usize> {
+    map_res(digit1, str::parse)(input)
+}
+
+/// The text typed on the command line, and what was typed before.
+#[derive(Debug, Default)]
+pub struct CmdLine {
+    pub input: String,
+    history: Vec<String>,
+    /// The history entry shown while browsing it.
+    browsing: Option<usize>,
+}
+
+impl CmdLine {
+    pub fn push(&mut self, c: char) {
+        self.input.push(c);
+        self.browsing = None;
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+        self.browsing = None;
+    }
+
+    /// Clears the input without running it.
+    pub fn cancel(&mut self) {
+        self.input.clear();
+        self.browsing = None;
+    }
+
+    /// Takes the input to run it, and remembers it.
+    pub fn take(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.browsing = None;
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        line
+    }
+
+    /// Shows the previous line from the history.
+    pub fn prev(&mut self) {
+        let idx = match self.browsing {
+            Some(idx) => idx.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.browsing = Some(idx);
+        self.input = self.history[idx].clone();
+    }
+
+    /// Shows the next line from the history, or an empty line past its end.
+    pub fn next(&mut self) {
+        match self.browsing {
+            Some(idx) if idx + 1 < self.history.len() => {
+                self.browsing = Some(idx + 1);
+                self.input = self.history[idx + 1].clone();
+            }
+            Some(_) => self.cancel(),
+            None => (),
+        }
+    }
+
+    /// Completes the command name, or the file name of commands that take
+    /// one, as far as all candidates agree. Lists the candidates when there
+    /// is nothing left to complete.
+    pub fn complete(&mut self) {
+        let candidates: Vec<String> = match self.input.split_once(' ') {
+            None => COMMANDS
+                .iter()
+                .filter(|cmd| cmd.starts_with(self.input.as_str()))
+                .map(|cmd| format!("{} ", cmd))
+                .collect(),
+            Some((cmd, arg)) if FILE_COMMANDS.contains(&cmd) => files(arg.trim_start())
+                .into_iter()
+                .map(|file| format!("{} {}", cmd, file))
+                .collect(),
+            Some(_) => vec![],
+        };
+        let Some(prefix) = common_prefix(&candidates) else {
+            return;
+        };
+        if prefix.len() > self.input.len() {
+            self.input = prefix;
+            self.browsing = None;
+        } else if candidates.len() > 1 {
+            log::info!("{}", candidates.join(" "));
+        }
+    }
+}
+
+/// Files and directories starting with `partial`, directories ending in `/`.
+fn files(partial: &str) -> Vec<String> {
+    let (dir, base) = match partial.rfind('/') {
+        Some(idx) => partial.split_at(idx + 1),
+        None => ("", partial),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return vec![];
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(base) || (name.starts_with('.') && !base.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn common_prefix(candidates: &[String]) -> Option<String> {
+    let (first, rest) = candidates.split_first()?;
+    let mut prefix = first.as_str();
+    for other in rest {
+        let len = prefix
+            .char_indices()
+            .zip(other.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((idx, _), _)| idx)
+            .unwrap_or_else(|| prefix.len().min(other.len()));
+        prefix = &prefix[..len];
+    }
+    Some(prefix.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CmdLine, EditorCommand};
+    use crate::util::Coord;
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn should_parse_commands() {
+        use EditorCommand::*;
+        assert_eq!(Some(Write(None)), EditorCommand::parse("w"));
+        assert_eq!(
+            Some(Write(Some(PathBuf::from("my song.orca")))),
+            EditorCommand::parse("write my song.orca")
+        );
+        assert_eq!(
+            Some(Edit(PathBuf::from("song.orca"))),
+            EditorCommand::parse(" e song.orca ")
+        );
+        assert_eq!(Some(Quit), EditorCommand::parse("q"));
+        assert_eq!(
+            Some(Resize { cols: 64, rows: 32 }),
+            EditorCommand::parse("resize 64 32")
+        );
+        assert_eq!(Some(Bpm(120)), EditorCommand::parse("bpm 120"));
+        assert_eq!(Some(Grid { x: 8, y: 4 }), EditorCommand::parse("grid 8 4"));
+        assert_eq!(
+            Some(Goto(Coord { x: 10, y: 4 })),
+            EditorCommand::parse("goto 10,4")
+        );
+        assert_eq!(
+            Some(Goto(Coord { x: 10, y: 4 })),
+            EditorCommand::parse("goto 10, 4")
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_commands() {
+        assert_eq!(None, EditorCommand::parse(""));
+        assert_eq!(None, EditorCommand::parse("e"));
+        assert_eq!(None, EditorCommand::parse("wq"));
+        assert_eq!(None, EditorCommand::parse("resize 64"));
+        assert_eq!(None, EditorCommand::parse("bpm fast"));
+        assert_eq!(None, EditorCommand::parse("goto 10"));
+    }
+
+    #[test]
+    fn should_browse_history() {
+        let mut line = CmdLine::default();
+        for cmd in ["bpm 90", "bpm 90", "goto 1,1"] {
+            line.input = cmd.to_string();
+            line.take();
+        }
+        line.next();
+        assert_eq!("", line.input);
+        line.prev();
+        assert_eq!("goto 1,1", line.input);
+        line.prev();
+        line.prev();
+        assert_eq!("bpm 90", line.input);
+        line.next();
+        assert_eq!("goto 1,1", line.input);
+        line.next();
+        assert_eq!("", line.input);
+    }
+
+    #[test]
+    fn should_complete_command_names() {
+        let mut line = CmdLine {
+            input: "re".to_string(),
+            ..Default::default()
+        };
+        line.complete();
+        assert_eq!("resize ", line.input);
+
+        line.input = "g".to_string();
+        line.complete();
+        assert_eq!("g", line.input);
+        line.input = "go".to_string();
+        line.complete();
+        assert_eq!("goto ", line.input);
+    }
+
+    #[test]
+    fn should_complete_file_names() {
+        let dir = std::env::temp_dir().join(format!("snorkel-{}-complete", std::process::id()));
+        fs::create_dir_all(dir.join("songs")).unwrap();
+        fs::write(dir.join("song.orca"), "").unwrap();
+        let dir = dir.display().to_string();
+
+        let mut line = CmdLine {
+            input: format!("e {}/so", dir),
+            ..Default::default()
+        };
+        line.complete();
+        assert_eq!(format!("e {}/song", dir), line.input);
+        line.input.push('s');
+        line.complete();
+        assert_eq!(format!("e {}/songs/", dir), line.input);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::{char, digit1},
+    combinator::{all_consuming, map, map_res, value},
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+/// Commands a patch can send to the running session through `$`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Bpm(usize),
+    Play,
+    Stop,
+    Frame(usize),
+    Write { text: String, x: usize, y: usize },
+    Load(String),
+}
+
+impl Command {
+    /// Parses a command such as `bpm:140` or `write:abc;4;2`.
+    pub fn parse(input: &str) -> Option<Command> {
+        all_consuming(command)(input).ok().map(|(_, cmd)| cmd)
+    }
+}
+
+fn command(input: &str) -> IResult<&str, Command> {
+    alt((
+        map(preceded(tag("bpm:"), number), Command::Bpm),
+        value(Command::Play, tag("play")),
+        value(Command::Stop, tag("stop")),
+        map(preceded(tag("frame:"), number), Command::Frame),
+        write,
+        map(preceded(tag("load:"), is_not(";")), |file: &str| {
+            Command::Load(file.to_string())
+        }),
+    ))(input)
+}
+
+fn write(input: &str) -> IResult<&str, Command> {
+    let (rest, (text, _, x, _, y)) = preceded(
+        tag("write:"),
+        tuple((is_not(";"), char(';'), number, char(';'), number)),
+    )(input)?;
+    let text = text.to_string();
+    Ok((rest, Command::Write { text, x, y }))
+}
+
+fn number(input: &str) -> IResult<&str, usize> {
+    map_res(digit1, str::parse)(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Command;
+
+    #[test]
+    fn should_parse_simple_commands() {
+        assert_eq!(Some(Command::Play), Command::parse("play"));
+        assert_eq!(Some(Command::Stop), Command::parse("stop"));
+        assert_eq!(Some(Command::Bpm(140)), Command::parse("bpm:140"));
+        assert_eq!(Some(Command::Frame(0)), Command::parse("frame:0"));
+        assert_eq!(
+            Some(Command::Load("song".to_string())),
+            Command::parse("load:song")
+        );
+    }
+
+    #[test]
+    fn should_parse_write_command() {
+        let expected = Command::Write {
+            text: "abc".to_string(),
+            x: 4,
+            y: 2,
+        };
+        assert_eq!(Some(expected), Command::parse("write:abc;4;2"));
+    }
+
+    #[test]
+    fn should_reject_malformed_commands() {
+        assert_eq!(None, Command::parse("bpm:"));
+        assert_eq!(None, Command::parse("bpm:fast"));
+        assert_eq!(None, Command::parse("playing"));
+        assert_eq!(None, Command::parse("write:abc;4"));
+        assert_eq!(None, Command::parse("load:"));
+        assert_eq!(None, Command::parse(""));
+    }
+}
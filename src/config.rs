@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use tui::style::{Color, Style};
 
 const DARK_GREY: Color = Color::Rgb(90, 90, 90);
@@ -58,10 +59,47 @@ impl Default for StyleConfig {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct OutputConfig {
+    pub osc_target: SocketAddr,
+    pub udp_target: SocketAddr,
+    pub midi_device: Option<String>,
+    /// Whether to send MIDI clock while playing.
+    pub midi_clock: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            osc_target: SocketAddr::from(([127, 0, 0, 1], 49162)),
+            udp_target: SocketAddr::from(([127, 0, 0, 1], 49161)),
+            midi_device: None,
+            midi_clock: false,
+        }
+    }
+}
+
+/// How the transport waits between frames.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum ClockBackend {
+    /// Sleep and spin for the last stretch, works everywhere
+    #[default]
+    SpinSleep,
+    /// A periodic linux timerfd
+    #[value(name = "timerfd")]
+    TimerFd,
+    /// Follow the MIDI clock of another device
+    Midi,
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub styles: StyleConfig,
     pub chars: CharConfig,
+    pub output: OutputConfig,
+    pub clock: ClockBackend,
+    /// Input device to follow with the `Midi` clock.
+    pub midi_input: Option<String>,
     pub grid_steps_x: u8,
     pub grid_steps_y: u8,
 }
@@ -71,6 +109,9 @@ impl Default for Config {
         Self {
             styles: StyleConfig::default(),
             chars: CharConfig::default(),
+            output: OutputConfig::default(),
+            clock: ClockBackend::default(),
+            midi_input: None,
             grid_steps_x: 8,
             grid_steps_y: 8,
         }
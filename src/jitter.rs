@@ -0,0 +1,102 @@
+use ndhistogram::{
+    axis::{BinInterval, Uniform},
+    ndhistogram, Hist1D, Histogram,
+};
+
+/// How far each transport tick was off from the interval it waited for, in
+/// microseconds. Positive values are late ticks.
+pub struct Jitter {
+    hist: Hist1D<Uniform<f64>>,
+    min: i64,
+    max: i64,
+    sum: i64,
+    count: u64,
+}
+
+impl Default for Jitter {
+    fn default() -> Self {
+        Self {
+            hist: ndhistogram!(Uniform::new(20, -1000.0, 1000.0)),
+            min: i64::MAX,
+            max: i64::MIN,
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Jitter {
+    pub fn record(&mut self, micros: i64) {
+        self.hist.fill(&(micros as f64));
+        self.min = self.min.min(micros);
+        self.max = self.max.max(micros);
+        self.sum += micros;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<i64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// Every bin of the histogram labeled with its lower bound, starting
+    /// with the underflow and ending with the overflow bin.
+    pub fn bins(&self) -> Vec<(String, u64)> {
+        self.hist
+            .iter()
+            .map(|item| {
+                let label = match item.bin {
+                    BinInterval::Underflow { end } => format!("<{}", end),
+                    BinInterval::Overflow { start } => format!(">{}", start),
+                    BinInterval::Bin { start, end: _ } => format!("{}", start),
+                };
+                (label, *item.value as u64)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Jitter;
+
+    #[test]
+    fn should_have_no_stats_without_samples() {
+        let jitter = Jitter::default();
+        assert_eq!(0, jitter.count());
+        assert_eq!(None, jitter.min());
+        assert_eq!(None, jitter.max());
+        assert_eq!(None, jitter.mean());
+        assert_eq!(22, jitter.bins().len());
+    }
+
+    #[test]
+    fn should_track_stats_and_bins() {
+        let mut jitter = Jitter::default();
+        for micros in [-50, 20, 20, 130, 5000] {
+            jitter.record(micros);
+        }
+        assert_eq!(5, jitter.count());
+        assert_eq!(Some(-50), jitter.min());
+        assert_eq!(Some(5000), jitter.max());
+        assert_eq!(Some(1024.0), jitter.mean());
+
+        let bins = jitter.bins();
+        assert_eq!(("<-1000".to_string(), 0), bins[0]);
+        assert_eq!(("-100".to_string(), 1), bins[10]);
+        assert_eq!(("0".to_string(), 2), bins[11]);
+        assert_eq!(("100".to_string(), 1), bins[12]);
+        assert_eq!((">1000".to_string(), 1), bins[21]);
+    }
+}
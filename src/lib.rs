@@ -7,13 +7,21 @@
 // TODO: turn this back on
 // #![warn(missing_docs)]
 
+pub mod cli;
+pub mod clock;
+pub mod cmdline;
+pub mod command;
 pub mod config;
+pub mod jitter;
 pub mod mode;
+pub mod msg;
 pub mod op;
+pub mod output;
 pub mod simple_loop;
 pub mod snorkel;
 pub mod state;
 pub mod timerfd;
+pub mod transport;
 pub mod ui;
 
 pub struct Event;
@@ -107,6 +115,95 @@ pub mod util {
         pub y: usize,
     }
 
+    /// The part of the grid that is on screen, `cols` x `rows` cells from
+    /// `x`,`y` on.
+    #[derive(Debug, Clone, Default, Eq, PartialEq)]
+    pub struct Viewport {
+        pub x: usize,
+        pub y: usize,
+        pub cols: usize,
+        pub rows: usize,
+    }
+
+    impl Viewport {
+        /// Sizes the viewport to `cols` x `rows` and scrolls it as little as
+        /// needed to show `cursor` on a grid of `grid_cols` x `grid_rows`.
+        pub fn follow(
+            &mut self,
+            cursor: &Coord,
+            cols: usize,
+            rows: usize,
+            grid_cols: usize,
+            grid_rows: usize,
+        ) {
+            self.cols = cmp::max(cols, 1);
+            self.rows = cmp::max(rows, 1);
+            self.x = scroll(self.x, cursor.x, self.cols, grid_cols);
+            self.y = scroll(self.y, cursor.y, self.rows, grid_rows);
+        }
+
+        /// Whether part of a grid of `grid_cols` x `grid_rows` is out of view.
+        pub fn clips(&self, grid_cols: usize, grid_rows: usize) -> bool {
+            grid_cols > self.cols || grid_rows > self.rows
+        }
+    }
+
+    /// The offset that keeps `pos` within `len` cells from it, without
+    /// scrolling past the end of the grid.
+    fn scroll(offset: usize, pos: usize, len: usize, grid_len: usize) -> usize {
+        let offset = if pos < offset {
+            pos
+        } else if pos >= offset + len {
+            pos + 1 - len
+        } else {
+            offset
+        };
+        cmp::min(offset, grid_len.saturating_sub(len))
+    }
+
+    #[cfg(test)]
+    mod viewport_tests {
+        use super::{Coord, Viewport};
+
+        #[test]
+        fn should_not_scroll_when_the_grid_fits() {
+            let mut view = Viewport::default();
+            view.follow(&Coord { x: 63, y: 31 }, 80, 40, 64, 32);
+            assert_eq!((0, 0), (view.x, view.y));
+            assert!(!view.clips(64, 32));
+        }
+
+        #[test]
+        fn should_follow_the_cursor() {
+            let mut view = Viewport::default();
+            view.follow(&Coord { x: 50, y: 20 }, 40, 16, 128, 64);
+            assert_eq!((11, 5), (view.x, view.y));
+            assert!(view.clips(128, 64));
+
+            // moving within the view does not scroll
+            view.follow(&Coord { x: 20, y: 10 }, 40, 16, 128, 64);
+            assert_eq!((11, 5), (view.x, view.y));
+
+            view.follow(&Coord { x: 3, y: 2 }, 40, 16, 128, 64);
+            assert_eq!((3, 2), (view.x, view.y));
+        }
+
+        #[test]
+        fn should_not_scroll_past_the_grid() {
+            let mut view = Viewport {
+                x: 100,
+                y: 60,
+                cols: 40,
+                rows: 16,
+            };
+            // e.g. after the grid shrank or the terminal grew
+            view.follow(&Coord { x: 100, y: 60 }, 40, 16, 128, 64);
+            assert_eq!((88, 48), (view.x, view.y));
+            view.follow(&Coord { x: 100, y: 60 }, 200, 100, 128, 64);
+            assert_eq!((0, 0), (view.x, view.y));
+        }
+    }
+
     #[cfg(test)]
     mod coord_tests {
         use super::Coord;
@@ -134,69 +231,443 @@ pub mod util {
 pub mod midi {
     use pm::MidiMessage;
     use portmidi as pm;
+    use std::sync::{Arc, Mutex};
 
-    const CLOCK_TICK: MidiMessage = MidiMessage {
+    pub const CLOCK_TICK: MidiMessage = MidiMessage {
         status: 0xF8,
         data1: 0x0,
         data2: 0x0,
         data3: 0x0,
     };
 
-    const START: MidiMessage = MidiMessage {
+    pub const START: MidiMessage = MidiMessage {
         status: 0xFA,
         data1: 0x0,
         data2: 0x0,
         data3: 0x0,
     };
 
-    const STOP: MidiMessage = MidiMessage {
+    pub const STOP: MidiMessage = MidiMessage {
         status: 0xFC,
         data1: 0x0,
         data2: 0x0,
         data3: 0x0,
     };
 
-    const SSP: MidiMessage = MidiMessage {
+    pub const CONTINUE: MidiMessage = MidiMessage {
+        status: 0xFB,
+        data1: 0x0,
+        data2: 0x0,
+        data3: 0x0,
+    };
+
+    pub const SSP: MidiMessage = MidiMessage {
         status: 0xF2,
         data1: 0x0,
         data2: 0x0,
         data3: 0x0,
     };
 
-    pub fn send_ssp<'a>(output: &mut pm::OutputPort<'a>) {
-        output.write_message(SSP).unwrap()
+    /// Ends a SysEx message.
+    const EOX: u8 = 0xF7;
+
+    /// Somewhere MIDI messages can be written to.
+    pub trait MidiSink {
+        fn write(&mut self, msg: MidiMessage) -> pm::Result<()>;
+
+        /// Writes `bytes` as they are, for data that is not a whole message.
+        fn write_raw(&mut self, bytes: &[u8]) -> pm::Result<()>;
+
+        /// Marks the start of a new frame.
+        fn frame(&mut self) {}
     }
 
-    pub fn send_clock_tick<'a>(output: &mut pm::OutputPort<'a>) {
-        output.write_message(CLOCK_TICK).unwrap()
+    impl<'a> MidiSink for pm::OutputPort<'a> {
+        fn write(&mut self, msg: MidiMessage) -> pm::Result<()> {
+            self.write_message(msg)
+        }
+
+        /// PortMidi only streams bytes verbatim through its SysEx writer,
+        /// which stops at an EOX. Short messages would be cut to the length
+        /// their status byte implies, and data bytes dropped altogether. An
+        /// EOX outside of a SysEx is ignored by receivers.
+        fn write_raw(&mut self, bytes: &[u8]) -> pm::Result<()> {
+            let mut msg = bytes.to_vec();
+            if msg.last() != Some(&EOX) {
+                msg.push(EOX);
+            }
+            self.write_sysex(0, &msg)
+        }
     }
 
-    pub fn send_start<'a>(output: &mut pm::OutputPort<'a>) {
-        output.write_message(START).unwrap()
+    /// Somewhere MIDI messages can be read from.
+    pub trait MidiSource {
+        /// The messages that arrived since the last read.
+        fn read_messages(&mut self) -> pm::Result<Vec<MidiMessage>>;
     }
 
-    pub fn send_stop<'a>(output: &mut pm::OutputPort<'a>) {
-        output.write_message(STOP).unwrap()
+    impl<'a> MidiSource for pm::InputPort<'a> {
+        fn read_messages(&mut self) -> pm::Result<Vec<MidiMessage>> {
+            Ok(self
+                .read_n(64)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|ev| ev.message)
+                .collect())
+        }
     }
 
-    pub fn send_note_on<'a>(output: &mut pm::OutputPort<'a>, note: u8) {
-        let note_on = MidiMessage {
-            status: 0x90, // note-on, chan 0
-            data1: note,  // middle c
-            data2: 127,   // full blast
+    /// Keeps every message and raw byte written to it grouped by frame, so
+    /// tests can check what a patch sends without any hardware. Clones share
+    /// their messages.
+    #[derive(Debug, Default, Clone)]
+    pub struct Recorder {
+        frames: Arc<Mutex<Vec<Vec<MidiMessage>>>>,
+        raw: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl Recorder {
+        /// The messages of every frame so far. Messages written before the
+        /// first frame get a frame of their own.
+        pub fn frames(&self) -> Vec<Vec<MidiMessage>> {
+            self.frames.lock().unwrap().clone()
+        }
+
+        /// The bytes written with `write_raw` in every frame so far, grouped
+        /// like [`Recorder::frames`].
+        pub fn raw(&self) -> Vec<Vec<u8>> {
+            self.raw.lock().unwrap().clone()
+        }
+    }
+
+    impl MidiSink for Recorder {
+        fn write(&mut self, msg: MidiMessage) -> pm::Result<()> {
+            let mut frames = self.frames.lock().unwrap();
+            match frames.last_mut() {
+                Some(frame) => frame.push(msg),
+                None => frames.push(vec![msg]),
+            }
+            Ok(())
+        }
+
+        fn write_raw(&mut self, bytes: &[u8]) -> pm::Result<()> {
+            let mut raw = self.raw.lock().unwrap();
+            match raw.last_mut() {
+                Some(frame) => frame.extend_from_slice(bytes),
+                None => raw.push(bytes.to_vec()),
+            }
+            Ok(())
+        }
+
+        fn frame(&mut self) {
+            self.frames.lock().unwrap().push(vec![]);
+            self.raw.lock().unwrap().push(vec![]);
+        }
+    }
+
+    pub fn send_ssp<S: MidiSink + ?Sized>(output: &mut S) -> pm::Result<()> {
+        output.write(SSP)
+    }
+
+    pub fn send_clock_tick<S: MidiSink + ?Sized>(output: &mut S) -> pm::Result<()> {
+        output.write(CLOCK_TICK)
+    }
+
+    pub fn send_start<S: MidiSink + ?Sized>(output: &mut S) -> pm::Result<()> {
+        output.write(START)
+    }
+
+    pub fn send_stop<S: MidiSink + ?Sized>(output: &mut S) -> pm::Result<()> {
+        output.write(STOP)
+    }
+
+    pub fn send_continue<S: MidiSink + ?Sized>(output: &mut S) -> pm::Result<()> {
+        output.write(CONTINUE)
+    }
+
+    pub fn send_note_on<S: MidiSink + ?Sized>(output: &mut S, note: u8) -> pm::Result<()> {
+        output.write(note_on(0, note, 127))
+    }
+
+    pub fn send_note_off<S: MidiSink + ?Sized>(output: &mut S, note: u8) -> pm::Result<()> {
+        output.write(note_off(0, note))
+    }
+
+    pub fn note_on(channel: u8, note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage {
+            status: 0x90 | (channel & 0x0F),
+            data1: note & 0x7F,
+            data2: velocity & 0x7F,
             data3: 0x0,
-        };
-        output.write_message(note_on).unwrap()
+        }
     }
 
-    pub fn send_note_off<'a>(output: &mut pm::OutputPort<'a>, note: u8) {
-        let note_off = MidiMessage {
-            status: 0x90, // note-on, chan 0
-            data1: note,  // middle c
-            data2: 0x0,   // off
+    pub fn note_off(channel: u8, note: u8) -> MidiMessage {
+        MidiMessage {
+            status: 0x80 | (channel & 0x0F),
+            data1: note & 0x7F,
+            data2: 0x0,
             data3: 0x0,
+        }
+    }
+
+    pub fn control_change(channel: u8, control: u8, value: u8) -> MidiMessage {
+        MidiMessage {
+            status: 0xB0 | (channel & 0x0F),
+            data1: control & 0x7F,
+            data2: value & 0x7F,
+            data3: 0x0,
+        }
+    }
+
+    /// Converts an octave and an Orca note glyph into a MIDI note number.
+    ///
+    /// Upper case glyphs are natural notes, lower case glyphs are sharps.
+    /// Glyphs past `G` keep counting up the scale into the next octaves,
+    /// so `C3` and `J2` both yield middle c (60).
+    pub fn note_number(octave: usize, note: char) -> Option<u8> {
+        let (name, offset) = match note {
+            'A' | 'H' => ('A', 0),
+            'a' | 'h' => ('a', 0),
+            'B' | 'I' => ('B', 0),
+            'C' => ('C', 0),
+            'c' => ('c', 0),
+            'D' => ('D', 0),
+            'd' => ('d', 0),
+            'E' => ('E', 0),
+            'F' | 'e' => ('F', 0),
+            'f' => ('f', 0),
+            'G' => ('G', 0),
+            'g' => ('g', 0),
+            'J' | 'b' | 'i' => ('C', 1),
+            'j' => ('c', 1),
+            'K' => ('D', 1),
+            'k' => ('d', 1),
+            'L' => ('E', 1),
+            'M' | 'l' => ('F', 1),
+            'm' => ('f', 1),
+            'N' => ('G', 1),
+            'n' => ('g', 1),
+            'O' => ('A', 1),
+            'o' => ('a', 1),
+            'P' => ('B', 1),
+            'Q' | 'p' => ('C', 2),
+            'q' => ('c', 2),
+            'R' => ('D', 2),
+            'r' => ('d', 2),
+            'S' => ('E', 2),
+            'T' | 's' => ('F', 2),
+            't' => ('f', 2),
+            'U' => ('G', 2),
+            'u' => ('g', 2),
+            'V' => ('A', 2),
+            'v' => ('a', 2),
+            'W' => ('B', 2),
+            'X' | 'w' => ('C', 3),
+            'x' => ('c', 3),
+            'Y' => ('D', 3),
+            'y' => ('d', 3),
+            'Z' => ('E', 3),
+            'z' => ('F', 3),
+            _ => return None,
+        };
+        let value = ['C', 'c', 'D', 'd', 'E', 'F', 'f', 'G', 'g', 'A', 'a', 'B']
+            .iter()
+            .position(|n| *n == name)?;
+        let octave = crate::util::clip(octave + offset, 0, 8);
+        Some(crate::util::clip(octave * 12 + value + 24, 0, 127) as u8)
+    }
+
+    /// Keeps track of sounding notes and when they need to be released.
+    #[derive(Debug, Default)]
+    pub struct NoteScheduler {
+        notes: Vec<ScheduledNote>,
+    }
+
+    #[derive(Debug)]
+    struct ScheduledNote {
+        channel: u8,
+        note: u8,
+        frames_left: usize,
+    }
+
+    impl NoteScheduler {
+        /// Schedules a note-off for `channel`/`note` after `length` frames.
+        ///
+        /// If the note is already sounding it is returned, so the caller can
+        /// release it before triggering it again.
+        pub fn start(&mut self, channel: u8, note: u8, length: usize) -> Option<(u8, u8)> {
+            let sounding = self
+                .notes
+                .iter()
+                .position(|n| n.channel == channel && n.note == note)
+                .map(|idx| self.notes.remove(idx))
+                .map(|n| (n.channel, n.note));
+            self.notes.push(ScheduledNote {
+                channel,
+                note,
+                frames_left: length,
+            });
+            sounding
+        }
+
+        /// Like `start`, but cuts every note sounding on `channel`, which are
+        /// returned for release.
+        pub fn start_mono(&mut self, channel: u8, note: u8, length: usize) -> Vec<(u8, u8)> {
+            let mut sounding = vec![];
+            self.notes.retain(|n| {
+                if n.channel == channel {
+                    sounding.push((n.channel, n.note));
+                    false
+                } else {
+                    true
+                }
+            });
+            self.notes.push(ScheduledNote {
+                channel,
+                note,
+                frames_left: length,
+            });
+            sounding
+        }
+
+        /// Advances the scheduler by one frame, returning the notes that
+        /// need to be released now.
+        pub fn advance(&mut self) -> Vec<(u8, u8)> {
+            let mut expired = vec![];
+            self.notes.retain_mut(|n| {
+                n.frames_left = n.frames_left.saturating_sub(1);
+                if n.frames_left == 0 {
+                    expired.push((n.channel, n.note));
+                    false
+                } else {
+                    true
+                }
+            });
+            expired
+        }
+
+        /// Forgets about all sounding notes, returning them for release.
+        pub fn release_all(&mut self) -> Vec<(u8, u8)> {
+            self.notes.drain(..).map(|n| (n.channel, n.note)).collect()
+        }
+    }
+
+    /// The names of all output devices.
+    pub fn output_names(pm: &pm::PortMidi) -> pm::Result<Vec<String>> {
+        Ok(pm
+            .devices()?
+            .into_iter()
+            .filter(|dev| dev.is_output())
+            .map(|dev| dev.name().clone())
+            .collect())
+    }
+
+    /// Opens the output device with the id `name`, or whose name contains
+    /// `name` (ignoring case), or the default output device when no name is
+    /// given.
+    pub fn output_port<'a>(
+        pm: &'a pm::PortMidi,
+        name: Option<&str>,
+    ) -> pm::Result<pm::OutputPort<'a>> {
+        let Some(name) = name else {
+            return pm.default_output_port(1024);
+        };
+        let device = match name.parse::<i32>() {
+            Ok(id) => pm.device(id)?,
+            Err(_) => {
+                let name = name.to_lowercase();
+                pm.devices()?
+                    .into_iter()
+                    .find(|dev| dev.is_output() && dev.name().to_lowercase().contains(&name))
+                    .ok_or(pm::Error::NotAnOutputDevice)?
+            }
+        };
+        pm.output_port(device, 1024)
+    }
+
+    /// Opens the output device called exactly `name`.
+    pub fn output_port_named<'a>(
+        pm: &'a pm::PortMidi,
+        name: &str,
+    ) -> pm::Result<pm::OutputPort<'a>> {
+        let device = pm
+            .devices()?
+            .into_iter()
+            .find(|dev| dev.is_output() && dev.name() == name)
+            .ok_or(pm::Error::NotAnOutputDevice)?;
+        pm.output_port(device, 1024)
+    }
+
+    /// Opens the input device whose name contains `name` (ignoring case),
+    /// or the default input device when no name is given.
+    pub fn input_port<'a>(
+        pm: &'a pm::PortMidi,
+        name: Option<&str>,
+    ) -> pm::Result<pm::InputPort<'a>> {
+        let Some(name) = name else {
+            return pm.default_input_port(1024);
         };
-        output.write_message(note_off).unwrap()
+        let name = name.to_lowercase();
+        let device = pm
+            .devices()?
+            .into_iter()
+            .find(|dev| dev.is_input() && dev.name().to_lowercase().contains(&name))
+            .ok_or(pm::Error::NotAnInputDevice)?;
+        pm.input_port(device, 1024)
+    }
+
+    #[cfg(test)]
+    mod note_tests {
+        use super::{note_number, NoteScheduler};
+
+        #[test]
+        fn note_number_should_match_orca() {
+            assert_eq!(Some(60), note_number(3, 'C'));
+            assert_eq!(Some(61), note_number(3, 'c'));
+            assert_eq!(Some(69), note_number(3, 'A'));
+            assert_eq!(Some(60), note_number(2, 'J'));
+            assert_eq!(Some(65), note_number(3, 'e'));
+            assert_eq!(Some(24), note_number(0, 'C'));
+            assert_eq!(None, note_number(3, '1'));
+        }
+
+        #[test]
+        fn note_number_should_clamp_octave() {
+            assert_eq!(note_number(8, 'C'), note_number(9, 'C'));
+            assert_eq!(note_number(8, 'C'), note_number(6, 'X'));
+        }
+
+        #[test]
+        fn scheduler_should_release_after_length_frames() {
+            let mut notes = NoteScheduler::default();
+            assert_eq!(None, notes.start(0, 60, 2));
+            assert_eq!(None, notes.start(1, 64, 1));
+            assert_eq!(vec![(1, 64)], notes.advance());
+            assert_eq!(vec![(0, 60)], notes.advance());
+            assert!(notes.advance().is_empty());
+        }
+
+        #[test]
+        fn scheduler_should_report_retriggered_notes() {
+            let mut notes = NoteScheduler::default();
+            assert_eq!(None, notes.start(0, 60, 4));
+            assert_eq!(Some((0, 60)), notes.start(0, 60, 1));
+            assert_eq!(vec![(0, 60)], notes.advance());
+            assert!(notes.release_all().is_empty());
+        }
+
+        #[test]
+        fn scheduler_should_cut_channel_for_mono_notes() {
+            let mut notes = NoteScheduler::default();
+            assert_eq!(None, notes.start(0, 60, 8));
+            assert_eq!(None, notes.start(0, 64, 8));
+            assert_eq!(None, notes.start(1, 60, 8));
+            assert_eq!(vec![(0, 60), (0, 64)], notes.start_mono(0, 62, 2));
+            assert!(notes.advance().is_empty());
+            assert_eq!(vec![(0, 62)], notes.advance());
+            assert_eq!(vec![(1, 60)], notes.release_all());
+        }
     }
 }
 
@@ -206,17 +677,51 @@ pub mod midi {
 
 pub mod linux {
     use portmidi as pm;
+    use std::io;
+
+    /// What `setup_rt_priority` managed to do. Errors are the OS error codes
+    /// of the failing calls.
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct RtPriority {
+        /// Raising `RLIMIT_RTPRIO` with `setrlimit`.
+        pub rlimit: Result<(), i32>,
+        /// Switching to `SCHED_FIFO` with `pthread_setschedparam`, with the
+        /// priority the thread runs at now.
+        pub sched: Result<i32, i32>,
+    }
+
+    impl RtPriority {
+        pub fn is_realtime(&self) -> bool {
+            self.sched.is_ok()
+        }
+    }
 
-    pub fn setup_rt_priority() {
+    /// Tries to run the current thread with real time priority. When that
+    /// fails the thread just keeps its normal priority.
+    pub fn setup_rt_priority() -> RtPriority {
         let limit = 50;
-        set_rt_prio_limit(limit);
-        unsafe {
+        let rlimit = set_rt_prio_limit(limit);
+        if let Err(err) = rlimit {
+            log::warn!(
+                "could not raise rt-prio limit: {}",
+                io::Error::from_raw_os_error(err)
+            );
+        }
+        let sched = unsafe {
             let thread = libc::pthread_self();
-            set_sched_param(thread, limit as libc::c_int);
+            set_sched_param(thread, limit as libc::c_int)
+        };
+        match sched {
+            Ok(priority) => log::info!("running with rt-priority {}", priority),
+            Err(err) => log::warn!(
+                "could not set rt-priority, running with normal priority: {}",
+                io::Error::from_raw_os_error(err)
+            ),
         }
+        RtPriority { rlimit, sched }
     }
 
-    unsafe fn set_sched_param(thread: libc::pthread_t, priority: libc::c_int) {
+    unsafe fn set_sched_param(thread: libc::pthread_t, priority: libc::c_int) -> Result<i32, i32> {
         let mut policy: libc::c_int = 0x0;
 
         let mut param = libc::sched_param {
@@ -224,9 +729,8 @@ pub mod linux {
         };
 
         let res = libc::pthread_getschedparam(thread, &mut policy, &mut param);
-
         if res != 0 {
-            eprintln!("getting sched_param failed: {}", res);
+            return Err(res);
         }
 
         // set up the desired policy before getting min/max values!
@@ -237,22 +741,14 @@ pub mod linux {
 
         param.sched_priority = crate::util::clip(priority, min, max);
 
-        let res = libc::pthread_setschedparam(thread, policy, &param);
-        if res != 0 {
-            eprintln!(
-                "could not set rt-prio {} on thread: {}",
-                param.sched_priority, res
-            );
-        } else {
-            println!(
-                "rt-priorities set up successfully with priority: {}",
-                param.sched_priority
-            )
+        match libc::pthread_setschedparam(thread, policy, &param) {
+            0 => Ok(param.sched_priority),
+            res => Err(res),
         }
     }
 
     /// Enables real time thread priorities in the current thread up to `limit`.
-    fn set_rt_prio_limit(limit: u64) {
+    fn set_rt_prio_limit(limit: u64) -> Result<(), i32> {
         let rt_limit_arg = libc::rlimit {
             rlim_cur: limit as libc::rlim_t,
             rlim_max: limit as libc::rlim_t,
@@ -260,8 +756,9 @@ pub mod linux {
         // Safe because the kernel doesn't modify memory that is accessible to the process here.
         let res = unsafe { libc::setrlimit(libc::RLIMIT_RTPRIO, &rt_limit_arg) };
         if res != 0 {
-            eprintln!("received {} from rlimit call", res)
+            return Err(io::Error::last_os_error().raw_os_error().unwrap_or(res));
         }
+        Ok(())
     }
 
     pub fn print_devices(pm: &pm::PortMidi) {
@@ -1,29 +1,93 @@
+use clap::Parser;
 use crossterm::{
     event::{self, Event},
     execute, terminal, Result,
 };
 use snorkel::{
-    state::{self, EditorState},
+    cli::{Args, Size},
+    clock, output,
+    state::{self, AppState, EditorState},
+    transport::Transport,
     ui,
 };
-use std::io;
-use tui::{backend::Backend, Terminal};
-
-fn ui_loop<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
-    let size = terminal.get_frame().size();
-    let rows = (size.height - 6) as usize;
-    let cols = (size.width - 10) as usize;
-    let mut state = state::AppState::new(rows, cols);
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tui::{
+    backend::{Backend, TestBackend},
+    Terminal,
+};
 
-    loop {
-        if state.edit_state == EditorState::QuitConfirmed {
-            return Ok(());
+fn setup(args: &Args, size: Size) -> io::Result<AppState> {
+    let mut state = state::AppState::new(size.rows, size.cols);
+    state.bpm = args.bpm as usize;
+    state.swing = args.swing;
+    state.config.output.midi_device = args.midi_device.clone();
+    state.config.output.midi_clock = args.midi_clock;
+    if let Some(target) = args.osc {
+        state.config.output.osc_target = target;
+    }
+    if let Some(target) = args.udp {
+        state.config.output.udp_target = target;
+    }
+    state.config.clock = args.clock;
+    state.config.midi_input = args.midi_input.clone();
+    state.fit_terminal = args.size.is_none() && !args.headless;
+    if let Some(path) = &args.file {
+        if path.exists() {
+            state.load(path)?;
+            state.fit_terminal = false;
+        } else {
+            state.file = Some(path.clone());
         }
+    }
+    Ok(state)
+}
 
-        terminal.draw(|frame| ui::render(frame, &state))?;
+/// The grid size that fits a terminal of `width` x `height`, next to the
+/// top bar and the margins.
+fn fit(width: u16, height: u16) -> Size {
+    Size {
+        cols: (width as usize).saturating_sub(10).max(1),
+        rows: (height as usize).saturating_sub(6).max(1),
+    }
+}
+
+fn ui_loop<B: Backend>(terminal: &mut Terminal<B>, state: &Mutex<AppState>) -> io::Result<()> {
+    // the editor is rendered in memory while the state is locked, and only
+    // written to the terminal after the transport can have the lock again
+    let size = terminal.size()?;
+    let mut offscreen = Terminal::new(TestBackend::new(size.width, size.height))?;
+    loop {
+        let size = terminal.size()?;
+        offscreen.backend_mut().resize(size.width, size.height);
+        let snapshot = {
+            let mut state = state.lock().unwrap();
+            if state.edit_state == EditorState::QuitConfirmed {
+                return Ok(());
+            }
+            offscreen
+                .draw(|frame| ui::render(frame, &mut state))?
+                .buffer
+                .clone()
+        };
+        terminal.draw(|frame| frame.render_widget(ui::Snapshot(&snapshot), frame.size()))?;
 
-        if let Event::Key(key) = event::read()? {
-            state.input(key);
+        // redraw regularly so the grid follows the transport
+        if event::poll(Duration::from_millis(16))? {
+            match event::read()? {
+                Event::Key(key) => state.lock().unwrap().input(key),
+                Event::Resize(width, height) => {
+                    let mut state = state.lock().unwrap();
+                    if state.fit_terminal {
+                        let size = fit(width, height);
+                        state.pad(size.cols, size.rows);
+                    }
+                }
+                _ => (),
+            }
         }
     }
 }
@@ -33,9 +97,32 @@ fn main() -> Result<()> {
     // ░▀▀█░░█░░█▀█░█▀▄░░█░░█░█░█▀▀
     // ░▀▀▀░░▀░░▀░▀░▀░▀░░▀░░▀▀▀░▀░░
 
+    let args = Args::parse();
+
     tui_logger::init_logger(log::LevelFilter::Info).unwrap();
     tui_logger::set_default_level(log::LevelFilter::Info);
 
+    let size = match (args.size, args.headless) {
+        (Some(size), _) => size,
+        (None, true) => Size::default(),
+        (None, false) => {
+            let (width, height) = terminal::size()?;
+            fit(width, height)
+        }
+    };
+    let mut state = setup(&args, size)?;
+    let tx = output::spawn(state.config.output.clone(), state.midi_devices.clone());
+    let clock = clock::open(state.config.clock, state.config.midi_input.as_deref(), &tx);
+    state.output = Some(tx);
+    state.play();
+    let state = Arc::new(Mutex::new(state));
+    let transport = Transport::spawn(state.clone(), clock);
+
+    if args.headless {
+        transport.join();
+        return Ok(());
+    }
+
     terminal::enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(
@@ -50,7 +137,8 @@ fn main() -> Result<()> {
     // ░█▀█░█▀▀░█▀▀
     // ░▀░▀░▀░░░▀░░
 
-    let res = ui_loop(&mut terminal);
+    let res = ui_loop(&mut terminal, &state);
+    transport.stop();
 
     // ░█▀▀░█░█░█░█░▀█▀░█▀▄░█▀█░█░█░█▀█
     // ░▀▀█░█▀█░█░█░░█░░█░█░█░█░█▄█░█░█
@@ -0,0 +1,40 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+pub struct CmdlineKeymap;
+
+impl CmdlineKeymap {
+    pub fn parse_key(ev: KeyEvent) -> Option<CmdlineModeCommand> {
+        if ev.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        let code = ev.code;
+        let modi = ev.modifiers;
+
+        match (code, modi) {
+            (KeyCode::Char('['), KeyModifiers::CONTROL) | (KeyCode::Esc, KeyModifiers::NONE) => {
+                Some(CmdlineModeCommand::Exit)
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => Some(CmdlineModeCommand::Run),
+            (KeyCode::Tab, KeyModifiers::NONE) => Some(CmdlineModeCommand::Complete),
+            (KeyCode::Up, KeyModifiers::NONE) => Some(CmdlineModeCommand::Prev),
+            (KeyCode::Down, KeyModifiers::NONE) => Some(CmdlineModeCommand::Next),
+            (KeyCode::Backspace, KeyModifiers::NONE) => Some(CmdlineModeCommand::Backspace),
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                Some(CmdlineModeCommand::Char(c))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CmdlineModeCommand {
+    Char(char),
+    Backspace,
+    Complete,
+    Prev,
+    Next,
+    Run,
+    Exit,
+}
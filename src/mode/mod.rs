@@ -1,10 +1,14 @@
+pub use cmdline::*;
 pub use insert::*;
 pub use normal::*;
+pub use picker::*;
 pub use replace::*;
 pub use select::*;
 
+mod cmdline;
 mod insert;
 mod normal;
+mod picker;
 mod replace;
 mod select;
 
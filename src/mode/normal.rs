@@ -11,17 +11,25 @@ pub enum NormalModeCommand {
     Paste,
     Move(Movement),
     ToggleLogs,
+    ToggleJitter,
+    PickDevice,
+    Save,
     ResetFrame,
     NextFrame,
+    TogglePlay,
+    Stop,
+    Swing(i8),
     EnterInsertMode,
     EnterReplaceMode,
     EnterSelectMode,
+    EnterCommandMode,
 }
 
 pub struct NormalKeymap;
 
 const REGULAR_MOVE: u8 = 1;
 const FAST_MOVE: u8 = 5;
+const SWING_STEP: i8 = 5;
 
 impl NormalKeymap {
     pub fn edit_state(ev: KeyEvent) -> Option<NormalModeCommand> {
@@ -114,7 +122,15 @@ impl NormalKeymap {
 
         match (code, modi) {
             (KeyCode::Char('p'), KeyModifiers::NONE) => Some(NormalModeCommand::Paste),
+            (KeyCode::Char(' '), KeyModifiers::NONE) => Some(NormalModeCommand::TogglePlay),
+            (KeyCode::Char('s'), KeyModifiers::NONE) => Some(NormalModeCommand::Stop),
+            (KeyCode::Char('<'), _) => Some(NormalModeCommand::Swing(-SWING_STEP)),
+            (KeyCode::Char('>'), _) => Some(NormalModeCommand::Swing(SWING_STEP)),
+            (KeyCode::Char(':'), _) => Some(NormalModeCommand::EnterCommandMode),
             (KeyCode::Char('l'), KeyModifiers::CONTROL) => Some(NormalModeCommand::ToggleLogs),
+            (KeyCode::Char('t'), KeyModifiers::CONTROL) => Some(NormalModeCommand::ToggleJitter),
+            (KeyCode::Char('o'), KeyModifiers::CONTROL) => Some(NormalModeCommand::PickDevice),
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => Some(NormalModeCommand::Save),
             (KeyCode::Char('f'), KeyModifiers::CONTROL) => Some(NormalModeCommand::NextFrame),
             (KeyCode::Char('r'), KeyModifiers::CONTROL) => Some(NormalModeCommand::ResetFrame),
             _ => None,
@@ -0,0 +1,36 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+pub struct PickerKeymap;
+
+impl PickerKeymap {
+    pub fn parse_key(ev: KeyEvent) -> Option<PickerModeCommand> {
+        if ev.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        let code = ev.code;
+        let modi = ev.modifiers;
+
+        match (code, modi) {
+            (KeyCode::Char('['), KeyModifiers::CONTROL)
+            | (KeyCode::Esc, KeyModifiers::NONE)
+            | (KeyCode::Char('q'), KeyModifiers::NONE) => Some(PickerModeCommand::Exit),
+            (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, KeyModifiers::NONE) => {
+                Some(PickerModeCommand::Down)
+            }
+            (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, KeyModifiers::NONE) => {
+                Some(PickerModeCommand::Up)
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => Some(PickerModeCommand::Choose),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PickerModeCommand {
+    Up,
+    Down,
+    Choose,
+    Exit,
+}
@@ -0,0 +1,39 @@
+use crate::command::Command;
+
+/// Messages emitted by operators during a `Snorkel::tick`, to be sent to
+/// the outside world by the `output` layer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Msg {
+    /// A note-on, followed by a note-off after `length` frames.
+    Note {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+        length: usize,
+    },
+    /// Like `Note`, but first releases whatever is sounding on `channel`.
+    MonoNote {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+        length: usize,
+    },
+    /// A control change, with `value` already scaled to 0..127.
+    ControlChange { channel: u8, knob: u8, value: u8 },
+    /// An OSC message with integer arguments.
+    Osc { path: String, args: Vec<i32> },
+    /// A raw string, sent as a single UDP datagram.
+    Udp(String),
+    /// A single raw byte for the MIDI output.
+    Byte(u8),
+    /// MIDI start, the transport plays from the top.
+    Start,
+    /// MIDI stop, releasing all sounding notes.
+    Stop,
+    /// MIDI continue, the transport resumes where it was paused.
+    Continue,
+    /// A MIDI clock pulse, 24 of which make a beat.
+    Clock,
+    /// A command for the running session itself, handled by `AppState`.
+    Command(Command),
+}
@@ -4,9 +4,12 @@ use crate::{config::CharConfig, util::Coord};
 pub enum Op {
     Add,
     Bang(usize),
+    Byte,
     Clock,
+    Command,
     Delay,
     Comment,
+    ControlChange,
     East(usize),
     Gen,
     Hold,
@@ -16,9 +19,13 @@ pub enum Op {
     Konkat,
     Lerp,
     Less,
+    Midi,
+    Mono,
     Mul,
     Val(char),
     North(usize),
+    Osc,
+    Pitch,
     Push,
     Query,
     Rand,
@@ -27,6 +34,7 @@ pub enum Op {
     Sub,
     Track,
     Uclid,
+    Udp,
     Var,
     West(usize),
     Write,
@@ -267,6 +275,14 @@ impl Op {
 
             '*' => Some(Op::Bang(frame)),
             '#' => Some(Op::Comment),
+            ':' => Some(Op::Midi),
+            '%' => Some(Op::Mono),
+            '=' => Some(Op::Osc),
+            ';' => Some(Op::Udp),
+            '$' => Some(Op::Command),
+            '/' => Some(Op::Byte),
+            '^' => Some(Op::Pitch),
+            '!' => Some(Op::ControlChange),
 
             // TODO: I'm lazy, but this should work for now
             c if c.is_alphanumeric() => Some(Op::Val(c)),
@@ -310,12 +326,25 @@ impl Op {
         }
     }
 
+    /// The character an operator was written as, which is how operators
+    /// read cells they use as text (e.g. note names). Empty results have
+    /// no glyph.
+    pub fn glyph(&self) -> Option<char> {
+        match self {
+            Op::EmptyResult(_) => None,
+            op => Some(op.as_char(&CharConfig::default())),
+        }
+    }
+
     pub fn as_char(&self, cfg: &CharConfig) -> char {
         match self {
             Op::Add => 'A',
             Op::Bang(_) => '*',
+            Op::Byte => '/',
             Op::Clock => 'C',
+            Op::Command => '$',
             Op::Comment => '#',
+            Op::ControlChange => '!',
             Op::Delay => 'D',
             Op::East(_) => 'E',
             Op::Gen => 'G',
@@ -326,9 +355,13 @@ impl Op {
             Op::Konkat => 'K',
             Op::Lerp => 'Z',
             Op::Less => 'L',
+            Op::Midi => ':',
+            Op::Mono => '%',
             Op::Mul => 'M',
             Op::Val(c) => *c,
             Op::North(_) => 'N',
+            Op::Osc => '=',
+            Op::Pitch => '^',
             Op::Push => 'P',
             Op::Query => 'Q',
             Op::Rand => 'R',
@@ -337,6 +370,7 @@ impl Op {
             Op::Sub => 'B',
             Op::Track => 'T',
             Op::Uclid => 'U',
+            Op::Udp => ';',
             Op::Var => 'V',
             Op::West(_) => 'W',
             Op::Write => 'X',
@@ -0,0 +1,664 @@
+use crate::{
+    clock::ClockMsg,
+    config::OutputConfig,
+    midi::{MidiSink, MidiSource, NoteScheduler},
+    msg::Msg,
+};
+use portmidi as pm;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// What the output thread is sent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Packet {
+    /// Everything emitted during one frame.
+    Frame(Vec<Msg>),
+    /// Transport and clock messages, which go out right away and do not
+    /// count towards note lengths.
+    Transport(Msg),
+    /// List the MIDI devices again and reopen the output.
+    Rescan,
+    /// Switch to the MIDI output device with exactly this name.
+    SelectDevice(String),
+    /// Pass on the MIDI clock from an input device.
+    Listen(Listener),
+}
+
+/// Asks the output thread to read an input device on its PortMidi context,
+/// since PortMidi must not be initialized twice.
+#[derive(Clone, Debug)]
+pub struct Listener {
+    /// Part of the input device's name, the default input when `None`.
+    pub device: Option<String>,
+    /// Where clock messages go, stamped with their arrival.
+    pub clock: mpsc::Sender<(ClockMsg, Instant)>,
+    /// Told whether the device could be opened.
+    pub opened: mpsc::Sender<pm::Result<()>>,
+}
+
+/// Channels cannot be compared, so listeners are told apart by their device.
+impl PartialEq for Listener {
+    fn eq(&self, other: &Self) -> bool {
+        self.device == other.device
+    }
+}
+
+impl Eq for Listener {}
+
+/// Which MIDI output device to open.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Wanted {
+    /// An id or part of a name, as given on the command line.
+    Query(String),
+    /// The exact name, as picked from the list of devices.
+    Name(String),
+}
+
+/// The MIDI outputs the output thread knows about, shared with the editor.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MidiDevices {
+    pub outputs: Vec<String>,
+    /// The device messages are sent to right now.
+    pub current: Option<String>,
+}
+
+/// Sends the messages produced by each frame to the configured outputs.
+pub struct Output<'a> {
+    midi: Option<Box<dyn MidiSink + 'a>>,
+    midi_lost: bool,
+    notes: NoteScheduler,
+    socket: Option<UdpSocket>,
+    osc_target: SocketAddr,
+    udp_target: SocketAddr,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(midi: Option<Box<dyn MidiSink + 'a>>, cfg: OutputConfig) -> Output<'a> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))
+            .map_err(|err| log::warn!("could not open udp socket: {}", err))
+            .ok();
+        Output {
+            midi,
+            midi_lost: false,
+            notes: NoteScheduler::default(),
+            socket,
+            osc_target: cfg.osc_target,
+            udp_target: cfg.udp_target,
+        }
+    }
+
+    /// Handles all messages emitted during one frame. Notes whose length
+    /// ran out are released before the new messages go out.
+    pub fn frame(&mut self, msgs: Vec<Msg>) {
+        if let Some(sink) = &mut self.midi {
+            sink.frame();
+        }
+        for (channel, note) in self.notes.advance() {
+            self.write_midi(crate::midi::note_off(channel, note));
+        }
+        for msg in msgs {
+            self.send(msg);
+        }
+    }
+
+    /// Sends a single message.
+    pub fn send(&mut self, msg: Msg) {
+        match msg {
+            Msg::Note {
+                channel,
+                note,
+                velocity,
+                length,
+            } => {
+                if let Some((channel, note)) = self.notes.start(channel, note, length) {
+                    self.write_midi(crate::midi::note_off(channel, note));
+                }
+                self.write_midi(crate::midi::note_on(channel, note, velocity));
+            }
+            Msg::MonoNote {
+                channel,
+                note,
+                velocity,
+                length,
+            } => {
+                for (channel, note) in self.notes.start_mono(channel, note, length) {
+                    self.write_midi(crate::midi::note_off(channel, note));
+                }
+                self.write_midi(crate::midi::note_on(channel, note, velocity));
+            }
+            Msg::ControlChange {
+                channel,
+                knob,
+                value,
+            } => self.write_midi(crate::midi::control_change(channel, knob, value)),
+            Msg::Osc { path, args } => self.write_osc(path, args),
+            Msg::Udp(text) => self.write_udp(text.as_bytes(), self.udp_target),
+            Msg::Byte(byte) => self.write_raw(&[byte]),
+            Msg::Start => {
+                self.write_midi(crate::midi::SSP);
+                self.write_midi(crate::midi::START);
+            }
+            Msg::Stop => {
+                self.release();
+                self.write_midi(crate::midi::STOP);
+            }
+            Msg::Continue => self.write_midi(crate::midi::CONTINUE),
+            Msg::Clock => self.write_midi(crate::midi::CLOCK_TICK),
+            // session commands are applied by `AppState` itself
+            Msg::Command(_) => (),
+        }
+    }
+
+    /// Releases all notes that are still sounding.
+    pub fn release(&mut self) {
+        for (channel, note) in self.notes.release_all() {
+            self.write_midi(crate::midi::note_off(channel, note));
+        }
+    }
+
+    /// Whether writing to the MIDI output failed, after which it is closed.
+    pub fn midi_lost(&self) -> bool {
+        self.midi_lost
+    }
+
+    fn write_midi(&mut self, msg: pm::MidiMessage) {
+        if let Some(sink) = &mut self.midi {
+            if let Err(err) = sink.write(msg) {
+                log::error!("could not write midi message: {}", err);
+                self.lose_midi();
+            }
+        }
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) {
+        if let Some(sink) = &mut self.midi {
+            if let Err(err) = sink.write_raw(bytes) {
+                log::error!("could not write midi bytes: {}", err);
+                self.lose_midi();
+            }
+        }
+    }
+
+    fn lose_midi(&mut self) {
+        self.midi = None;
+        self.midi_lost = true;
+    }
+
+    fn write_osc(&mut self, addr: String, args: Vec<i32>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr,
+            args: args.into_iter().map(OscType::Int).collect(),
+        });
+        let bytes = match rosc::encoder::encode(&packet) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                log::error!("could not encode osc message: {}", err);
+                return;
+            }
+        };
+        self.write_udp(&bytes, self.osc_target);
+    }
+
+    fn write_udp(&self, bytes: &[u8], target: SocketAddr) {
+        if let Some(socket) = &self.socket {
+            if let Err(err) = socket.send_to(bytes, target) {
+                log::error!("could not send to {}: {}", target, err);
+            }
+        }
+    }
+}
+
+/// How often to look for a MIDI device while there is none.
+const RETRY: Duration = Duration::from_secs(1);
+
+/// How often to read the MIDI input while there is one.
+const POLL: Duration = Duration::from_micros(250);
+
+/// An open MIDI input, whose clock messages are passed on.
+struct Input<'a> {
+    source: Box<dyn MidiSource + 'a>,
+    clock: mpsc::Sender<(ClockMsg, Instant)>,
+}
+
+impl Input<'_> {
+    /// Passes on the clock messages that arrived. Returns false once the
+    /// input or the clock that follows it is gone.
+    fn poll(&mut self) -> bool {
+        match self.source.read_messages() {
+            Ok(msgs) => {
+                let now = Instant::now();
+                msgs.into_iter()
+                    .filter_map(ClockMsg::parse)
+                    .all(|msg| self.clock.send((msg, now)).is_ok())
+            }
+            Err(err) => {
+                log::error!("could not read midi input: {}", err);
+                false
+            }
+        }
+    }
+}
+
+/// Opens the input device `listener` asks for, telling it whether that
+/// worked.
+fn listen<'a>(pm: Option<&'a pm::PortMidi>, listener: Listener) -> Option<Input<'a>> {
+    let port = pm
+        .ok_or(pm::Error::Unknown)
+        .and_then(|pm| crate::midi::input_port(pm, listener.device.as_deref()));
+    let _ = listener
+        .opened
+        .send(port.as_ref().map(|_| ()).map_err(|err| *err));
+    let port = port.ok()?;
+    log::info!("following midi clock from {}", port.device().name());
+    Some(Input {
+        source: Box::new(port),
+        clock: listener.clock,
+    })
+}
+
+/// How a MIDI session of the output thread ended.
+#[derive(Debug, Eq, PartialEq)]
+enum Session {
+    /// The channel was closed.
+    Done,
+    /// Devices changed, start over.
+    Reopen,
+    /// There is still no device, look again.
+    Retry,
+}
+
+/// Spawns the output thread on the configured MIDI output device. The
+/// devices it finds are published in `devices`. When there is no device, or
+/// it goes away, the thread keeps looking for one instead of giving up.
+pub fn spawn(cfg: OutputConfig, devices: Arc<Mutex<MidiDevices>>) -> mpsc::Sender<Packet> {
+    let (tx, rx) = mpsc::channel::<Packet>();
+    thread::spawn(move || {
+        // PortMidi is initialized and terminated for the whole process, so
+        // a single context is kept for as long as the thread runs
+        let pm = pm::PortMidi::new()
+            .map_err(|err| log::warn!("could not initialize PortMidi: {}", err))
+            .ok();
+        let mut input = None;
+        let mut device = cfg.midi_device.clone().map(Wanted::Query);
+        let mut quiet = false;
+        loop {
+            match session(
+                pm.as_ref(),
+                &mut input,
+                &cfg,
+                &mut device,
+                &devices,
+                &rx,
+                quiet,
+            ) {
+                Session::Done => break,
+                Session::Reopen => quiet = false,
+                Session::Retry => quiet = true,
+            }
+        }
+    });
+    tx
+}
+
+/// Lists the devices and runs the output on the wanted one until the devices
+/// need to be looked at again. The input outlives the session.
+fn session<'a>(
+    pm: Option<&'a pm::PortMidi>,
+    input: &mut Option<Input<'a>>,
+    cfg: &OutputConfig,
+    device: &mut Option<Wanted>,
+    devices: &Mutex<MidiDevices>,
+    rx: &mpsc::Receiver<Packet>,
+    quiet: bool,
+) -> Session {
+    let port = pm.and_then(|pm| {
+        let mut devices = devices.lock().unwrap();
+        devices.outputs = crate::midi::output_names(pm).unwrap_or_default();
+        let port = match device {
+            None => crate::midi::output_port(pm, None),
+            Some(Wanted::Query(query)) => crate::midi::output_port(pm, Some(query)),
+            Some(Wanted::Name(name)) => crate::midi::output_port_named(pm, name),
+        };
+        match port {
+            Ok(port) => {
+                log::info!("sending midi to {}", port.device().name());
+                devices.current = Some(port.device().name().clone());
+                Some(port)
+            }
+            Err(err) => {
+                if !quiet {
+                    log::warn!("no midi output available: {}", err);
+                }
+                devices.current = None;
+                None
+            }
+        }
+    });
+    // keep looking until a device shows up
+    let retry = port.is_none();
+
+    let sink = port.map(|port| Box::new(port) as Box<dyn MidiSink>);
+    let mut output = Output::new(sink, cfg.clone());
+    let next = serve(&mut output, input, pm, rx, retry, device);
+    output.release();
+    next
+}
+
+/// Feeds packets to `output` until the devices need to be looked at again,
+/// after [`RETRY`] when `retry` is set. The input is read in between packets.
+fn serve<'a>(
+    output: &mut Output,
+    input: &mut Option<Input<'a>>,
+    pm: Option<&'a pm::PortMidi>,
+    rx: &mpsc::Receiver<Packet>,
+    retry: bool,
+    device: &mut Option<Wanted>,
+) -> Session {
+    let retry_at = retry.then(|| Instant::now() + RETRY);
+    loop {
+        let timeout = match (&input, retry_at) {
+            (Some(_), _) => Some(POLL),
+            (None, Some(at)) => Some(at.saturating_duration_since(Instant::now())),
+            (None, None) => None,
+        };
+        let packet = match timeout {
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(packet) => Some(packet),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break Session::Done,
+            },
+            None => match rx.recv() {
+                Ok(packet) => Some(packet),
+                Err(_) => break Session::Done,
+            },
+        };
+        if input.as_mut().is_some_and(|input| !input.poll()) {
+            *input = None;
+        }
+        match packet {
+            Some(Packet::Frame(msgs)) => output.frame(msgs),
+            Some(Packet::Transport(msg)) => output.send(msg),
+            Some(Packet::Rescan) => break Session::Reopen,
+            Some(Packet::SelectDevice(name)) => {
+                *device = Some(Wanted::Name(name));
+                break Session::Reopen;
+            }
+            Some(Packet::Listen(listener)) => *input = listen(pm, listener),
+            None => (),
+        }
+        if output.midi_lost() {
+            log::warn!("lost midi output, waiting for it to come back");
+            break Session::Reopen;
+        }
+        if retry_at.is_some_and(|at| Instant::now() >= at) {
+            break Session::Retry;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serve, session, Input, Listener, MidiDevices, Output, Packet, Session, Wanted};
+    use crate::{
+        clock::{ClockMsg, Sync},
+        config::OutputConfig,
+        midi::{self, MidiSink, MidiSource, Recorder},
+        msg::Msg,
+        snorkel::Snorkel,
+    };
+    use portmidi as pm;
+    use rosc::{OscPacket, OscType};
+    use std::{
+        net::UdpSocket,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    /// A device that was unplugged, counting the writes it gets.
+    struct Unplugged(Arc<AtomicUsize>);
+
+    impl MidiSink for Unplugged {
+        fn write(&mut self, _msg: pm::MidiMessage) -> pm::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(pm::Error::Invalid)
+        }
+
+        fn write_raw(&mut self, _bytes: &[u8]) -> pm::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(pm::Error::Invalid)
+        }
+    }
+
+    /// An input device, playing the messages sent to it.
+    struct Feed(mpsc::Receiver<pm::MidiMessage>);
+
+    impl MidiSource for Feed {
+        fn read_messages(&mut self) -> pm::Result<Vec<pm::MidiMessage>> {
+            Ok(self.0.try_iter().collect())
+        }
+    }
+
+    fn listener() -> UdpSocket {
+        let socket = UdpSocket::bind(("127.0.0.1", 0)).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        socket
+    }
+
+    #[test]
+    fn osc_message_should_be_sent_to_target() {
+        let socket = listener();
+        let cfg = OutputConfig {
+            osc_target: socket.local_addr().unwrap(),
+            ..OutputConfig::default()
+        };
+        let mut output = Output::new(None, cfg);
+        output.frame(vec![Msg::Osc {
+            path: "/a".to_string(),
+            args: vec![1, 35],
+        }]);
+
+        let mut buf = [0u8; 1024];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        match rosc::decoder::decode_udp(&buf[..len]).unwrap().1 {
+            OscPacket::Message(msg) => {
+                assert_eq!("/a", msg.addr);
+                assert_eq!(vec![OscType::Int(1), OscType::Int(35)], msg.args);
+            }
+            other => panic!("expected a message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn udp_string_should_be_sent_to_target() {
+        let socket = listener();
+        let cfg = OutputConfig {
+            udp_target: socket.local_addr().unwrap(),
+            ..OutputConfig::default()
+        };
+        let mut output = Output::new(None, cfg);
+        output.frame(vec![Msg::Udp("hello".to_string())]);
+
+        let mut buf = [0u8; 1024];
+        let (len, _) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(b"hello", &buf[..len]);
+    }
+
+    #[test]
+    fn patch_should_send_notes_on_expected_frames() {
+        let mut snrkl = Snorkel::from_orca(".D2...\n..:03C\n", 0);
+        let recorder = Recorder::default();
+        let mut output = Output::new(Some(Box::new(recorder.clone())), OutputConfig::default());
+        for frame in 0..4 {
+            snrkl.frame = frame;
+            snrkl.tick();
+            output.frame(snrkl.drain_msgs());
+        }
+
+        let on = midi::note_on(0, 60, 119);
+        let off = midi::note_off(0, 60);
+        assert_eq!(
+            vec![vec![on], vec![off], vec![on], vec![off]],
+            recorder.frames()
+        );
+    }
+
+    #[test]
+    fn bytes_should_reach_the_device_as_they_are() {
+        let recorder = Recorder::default();
+        let mut output = Output::new(Some(Box::new(recorder.clone())), OutputConfig::default());
+        output.frame(vec![Msg::Byte(0x0C), Msg::Byte(0xFA)]);
+        assert_eq!(vec![vec![0x0C, 0xFA]], recorder.raw());
+        assert_eq!(vec![Vec::<portmidi::MidiMessage>::new()], recorder.frames());
+    }
+
+    #[test]
+    fn banged_pitch_should_reach_the_device() {
+        let mut snrkl = Snorkel::from_orca(".D2...\n..^3A.\n", 0);
+        let recorder = Recorder::default();
+        let mut output = Output::new(Some(Box::new(recorder.clone())), OutputConfig::default());
+        for frame in 0..4 {
+            snrkl.frame = frame;
+            snrkl.tick();
+            output.frame(snrkl.drain_msgs());
+        }
+        assert_eq!(vec![vec![69], vec![], vec![69], vec![]], recorder.raw());
+    }
+
+    #[test]
+    fn failing_device_should_be_dropped_and_reopened() {
+        let writes = Arc::new(AtomicUsize::new(0));
+        let sink = Unplugged(writes.clone());
+        let mut output = Output::new(Some(Box::new(sink)), OutputConfig::default());
+        let (tx, rx) = mpsc::channel();
+        tx.send(Packet::Transport(Msg::Clock)).unwrap();
+        tx.send(Packet::Transport(Msg::Clock)).unwrap();
+
+        let mut device = None;
+        assert_eq!(
+            Session::Reopen,
+            serve(&mut output, &mut None, None, &rx, false, &mut device)
+        );
+        assert!(output.midi_lost());
+        assert_eq!(None, device);
+        // the sink is gone, so nothing else is written to it
+        output.send(Msg::Clock);
+        output.release();
+        assert_eq!(1, writes.load(Ordering::SeqCst));
+        assert_eq!(1, Arc::strong_count(&writes));
+
+        // the second packet is still served by the next session
+        assert_eq!(Some(Packet::Transport(Msg::Clock)), rx.try_recv().ok());
+    }
+
+    #[test]
+    fn picked_device_should_be_opened_by_exact_name() {
+        let mut output = Output::new(None, OutputConfig::default());
+        let (tx, rx) = mpsc::channel();
+        tx.send(Packet::SelectDevice("Synth".to_string())).unwrap();
+        let mut device = Some(Wanted::Query("mini".to_string()));
+        assert_eq!(
+            Session::Reopen,
+            serve(&mut output, &mut None, None, &rx, false, &mut device)
+        );
+        assert_eq!(Some(Wanted::Name("Synth".to_string())), device);
+    }
+
+    #[test]
+    fn missing_device_should_be_looked_for_again() {
+        let mut output = Output::new(None, OutputConfig::default());
+        let (_tx, rx) = mpsc::channel();
+        assert_eq!(
+            Session::Retry,
+            serve(&mut output, &mut None, None, &rx, true, &mut None)
+        );
+    }
+
+    #[test]
+    fn clock_input_should_reach_the_clock() {
+        let (feed, msgs) = mpsc::channel();
+        let (clock, pulses) = mpsc::channel();
+        let mut input = Some(Input {
+            source: Box::new(Feed(msgs)),
+            clock,
+        });
+        feed.send(midi::START).unwrap();
+        feed.send(midi::note_on(0, 60, 100)).unwrap();
+        feed.send(midi::CLOCK_TICK).unwrap();
+
+        let mut output = Output::new(None, OutputConfig::default());
+        let (tx, rx) = mpsc::channel();
+        tx.send(Packet::Rescan).unwrap();
+        let next = serve(&mut output, &mut input, None, &rx, false, &mut None);
+        assert_eq!(Session::Reopen, next);
+        assert_eq!(
+            vec![ClockMsg::Sync(Sync::Start), ClockMsg::Pulse],
+            pulses.try_iter().map(|(msg, _)| msg).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn listening_without_portmidi_should_fail() {
+        let mut output = Output::new(None, OutputConfig::default());
+        let (tx, rx) = mpsc::channel();
+        let (clock, _pulses) = mpsc::channel();
+        let (opened, result) = mpsc::channel();
+        tx.send(Packet::Listen(Listener {
+            device: None,
+            clock,
+            opened,
+        }))
+        .unwrap();
+        drop(tx);
+
+        let mut input = None;
+        let next = serve(&mut output, &mut input, None, &rx, false, &mut None);
+        assert_eq!(Session::Done, next);
+        assert!(input.is_none());
+        assert_eq!(Err(pm::Error::Unknown), result.recv().unwrap());
+    }
+
+    #[test]
+    fn reopening_should_keep_the_input_open() {
+        let (feed, msgs) = mpsc::channel();
+        let (clock, pulses) = mpsc::channel();
+        let mut input = Some(Input {
+            source: Box::new(Feed(msgs)),
+            clock,
+        });
+        let devices = Mutex::new(MidiDevices::default());
+        let (tx, rx) = mpsc::channel();
+        let mut device = None;
+        for packet in [Packet::Rescan, Packet::SelectDevice("Synth".to_string())] {
+            tx.send(packet).unwrap();
+            let cfg = OutputConfig::default();
+            let next = session(None, &mut input, &cfg, &mut device, &devices, &rx, true);
+            assert_eq!(Session::Reopen, next);
+            feed.send(midi::CLOCK_TICK).unwrap();
+        }
+
+        // a lost output device is reopened too
+        let sink = Unplugged(Arc::new(AtomicUsize::new(0)));
+        let mut output = Output::new(Some(Box::new(sink)), OutputConfig::default());
+        tx.send(Packet::Transport(Msg::Clock)).unwrap();
+        let next = serve(&mut output, &mut input, None, &rx, false, &mut device);
+        assert_eq!(Session::Reopen, next);
+
+        // every pulse got through, so the input was read all along
+        assert!(input.is_some());
+        assert!(feed.send(midi::CLOCK_TICK).is_ok());
+        assert_eq!(2, pulses.try_iter().count());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::clock::{Clock, SpinSleepClock};
 use ndhistogram::{axis::Uniform, ndhistogram, Histogram};
 use portmidi as pm;
 use std::{
@@ -5,12 +6,21 @@ use std::{
     time::{Duration, Instant},
 };
 
-pub fn run(rx: mpsc::Receiver<crate::Event>) {
+pub fn run(rx: mpsc::Receiver<crate::Event>, device: Option<&str>) -> pm::Result<()> {
+    run_with(rx, device, SpinSleepClock::new())
+}
+
+/// Sends MIDI clock and a note on every beat at 120 bpm, waiting on `clock`
+/// between ticks, and prints how late the waits were once stopped.
+pub fn run_with<C: Clock>(
+    rx: mpsc::Receiver<crate::Event>,
+    device: Option<&str>,
+    mut clock: C,
+) -> pm::Result<()> {
     crate::linux::setup_rt_priority();
 
-    let pm = pm::PortMidi::new().expect("could not initialize PortMidi");
-    let device = pm.device(0i32).expect("Could not get device 0");
-    let mut output = pm.output_port(device, 1024).unwrap();
+    let pm = pm::PortMidi::new()?;
+    let mut output = crate::midi::output_port(&pm, device)?;
 
     // snorkel::print_devices(&pm);
 
@@ -18,13 +28,10 @@ pub fn run(rx: mpsc::Receiver<crate::Event>) {
     let tpb = 24 * bpm;
     let dur = Duration::from_micros(60_000_000 / tpb);
 
-    crate::midi::send_ssp(&mut output);
-    crate::midi::send_start(&mut output);
-
-    let mut hist = ndhistogram!(Uniform::new(20, 0.0, 1000.0));
+    crate::midi::send_ssp(&mut output)?;
+    crate::midi::send_start(&mut output)?;
 
-    let sleeper = spin_sleep::SpinSleeper::new(100_000);
-    sleeper.sleep(dur);
+    let mut hist = ndhistogram!(Uniform::new(20, -100.0, 1000.0));
 
     // ░█░░░█▀█░█▀█░█▀█
     // ░█░░░█░█░█░█░█▀▀
@@ -33,28 +40,30 @@ pub fn run(rx: mpsc::Receiver<crate::Event>) {
     let mut start = Instant::now();
     let mut iter = 0u64;
     'inner: loop {
-        crate::midi::send_clock_tick(&mut output);
+        crate::midi::send_clock_tick(&mut output)?;
 
         if iter % 24 == 0 {
-            crate::midi::send_note_on(&mut output, 34);
+            crate::midi::send_note_on(&mut output, 34)?;
         }
 
         iter += 1;
 
-        sleeper.sleep(dur);
+        clock.wait(dur);
 
-        // record how long we slept
-        hist.fill(&((start.elapsed().as_micros() - dur.as_micros()) as f32));
+        // record how late we woke up
+        let jitter = (start.elapsed().as_micros() as i64) - (dur.as_micros() as i64);
+        hist.fill(&(jitter as f32));
         start = Instant::now();
 
-        crate::midi::send_note_off(&mut output, 34);
+        crate::midi::send_note_off(&mut output, 34)?;
 
-        if let Ok(_) = rx.try_recv() {
+        if rx.try_recv().is_ok() {
             break 'inner;
         }
     }
 
-    crate::midi::send_stop(&mut output);
+    crate::midi::send_stop(&mut output)?;
 
     println!("histogram: {}", hist);
+    Ok(())
 }
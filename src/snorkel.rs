@@ -1,4 +1,7 @@
 use crate::{
+    command::Command,
+    midi,
+    msg::Msg,
     op::Op,
     state::UndoOp,
     util::{Coord, Selection},
@@ -12,6 +15,8 @@ pub struct Snorkel {
     pub frame: usize,
     vars: HashMap<char, Op>,
     data: Vec<Vec<Option<Op>>>,
+    locks: Vec<Vec<bool>>,
+    msgs: Vec<Msg>,
 }
 
 impl Snorkel {
@@ -23,6 +28,7 @@ impl Snorkel {
         for _ in 0..rows {
             data.push(vec![None; cols]);
         }
+        let locks = vec![vec![false; cols]; rows];
         let frame = 0;
         let vars = HashMap::new();
         assert_eq!(data.len(), rows);
@@ -30,6 +36,8 @@ impl Snorkel {
             rows,
             cols,
             data,
+            locks,
+            msgs: Vec::new(),
             frame,
             vars,
         }
@@ -39,73 +47,86 @@ impl Snorkel {
     // ░░█░░░█░░█░░░█▀▄
     // ░░▀░░▀▀▀░▀▀▀░▀░▀
     pub fn tick(&mut self) {
+        self.reset_locks();
         let mut coord = Coord { x: 0, y: 0 };
         for y in 0..self.rows {
             for x in 0..self.cols {
                 coord.x = x;
                 coord.y = y;
+                // cells written or read as inputs earlier in this frame are
+                // locked and must not be evaluated again until the next tick
+                if self.is_locked(&coord) {
+                    continue;
+                }
                 match self.get_cell(&coord) {
                     // ░█▀█░█▀▄░█▀▄
                     // ░█▀█░█░█░█░█
                     // ░▀░▀░▀▀░░▀▀░
                     Some(Op::Add) => {
+                        self.lock_right(&coord, 1);
                         if let Some(result) = self.op_add(&coord) {
                             coord.y += 1;
-                            let _ignored = self.set_cell(&coord, result);
+                            let _ignored = self.write(&coord, result);
                         }
                     }
                     // ░█▀▀░█░█░█▀▄
                     // ░▀▀█░█░█░█▀▄
                     // ░▀▀▀░▀▀▀░▀▀░
                     Some(Op::Sub) => {
+                        self.lock_right(&coord, 1);
                         if let Some(result) = self.op_sub(&coord) {
                             coord.y += 1;
-                            let _ignored = self.set_cell(&coord, result);
+                            let _ignored = self.write(&coord, result);
                         }
                     }
                     // ░█▄█░█░█░█░░
                     // ░█░█░█░█░█░░
                     // ░▀░▀░▀▀▀░▀▀▀
                     Some(Op::Mul) => {
+                        self.lock_right(&coord, 1);
                         let op = self.op_mul(&coord);
                         coord.y += 1;
-                        let _ignored = self.set_cell(&coord, op);
+                        let _ignored = self.write(&coord, op);
                     }
                     // ░█▀▄░█▀█░█▀█░█▀▄
                     // ░█▀▄░█▀█░█░█░█░█
                     // ░▀░▀░▀░▀░▀░▀░▀▀░
                     Some(Op::Rand) => {
+                        self.lock_right(&coord, 1);
                         let op = self.op_rand(&coord);
                         coord.y += 1;
-                        let _ignored = self.set_cell(&coord, op);
+                        let _ignored = self.write(&coord, op);
                     }
                     // ░▀█▀░█▀▀
                     // ░░█░░█▀▀
                     // ░▀▀▀░▀░░
                     Some(Op::If) => {
+                        self.lock_right(&coord, 1);
                         if let Some(result) = self.op_if(&coord) {
                             coord.y += 1;
-                            let _ignored = self.set_cell(&coord, result);
+                            let _ignored = self.write(&coord, result);
                         }
                     }
                     // ░█▀▀░█░░░█▀█░█▀▀░█░█
                     // ░█░░░█░░░█░█░█░░░█▀▄
                     // ░▀▀▀░▀▀▀░▀▀▀░▀▀▀░▀░▀
                     Some(Op::Clock) => {
+                        self.lock_right(&coord, 1);
                         if let Some(result) = self.op_clock(&coord) {
                             coord.y += 1;
-                            let _ignored = self.set_cell(&coord, result);
+                            let _ignored = self.write(&coord, result);
                         }
                     }
                     // ░█▀▄░█▀▀░█░░░█▀█░█░█
                     // ░█░█░█▀▀░█░░░█▀█░░█░
                     // ░▀▀░░▀▀▀░▀▀▀░▀░▀░░▀░
                     Some(Op::Delay) => {
+                        self.lock_right(&coord, 1);
                         let mut below = coord.clone();
                         below.y += 1;
                         let _ignored = match self.op_delay(&coord) {
-                            Some(op) => self.set_cell(&below, op),
-                            None => self.del_cell(&below),
+                            Some(op) => self.write(&below, op),
+                            None => self.erase(&below),
                         };
                     }
                     // ░█▀▀░█▀█░█▀▀░▀█▀
@@ -115,11 +136,11 @@ impl Snorkel {
                         if frame != self.frame {
                             let op = self.op_east(&coord);
                             if op.is_bang() {
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             } else {
-                                let _ignored = self.del_cell(&coord);
+                                let _ignored = self.erase(&coord);
                                 coord.x += 1;
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             };
                         }
                     }
@@ -130,11 +151,11 @@ impl Snorkel {
                         if frame != self.frame {
                             let op = self.op_west(&coord);
                             if op.is_bang() {
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             } else {
-                                let _ignored = self.del_cell(&coord);
+                                let _ignored = self.erase(&coord);
                                 coord.x -= 1;
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             };
                         }
                     }
@@ -145,11 +166,11 @@ impl Snorkel {
                         if frame != self.frame {
                             let op = self.op_north(&coord);
                             if op.is_bang() {
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             } else {
-                                let _ignored = self.del_cell(&coord);
+                                let _ignored = self.erase(&coord);
                                 coord.y -= 1;
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             };
                         }
                     }
@@ -160,11 +181,11 @@ impl Snorkel {
                         if frame != self.frame {
                             let op = self.op_south(&coord);
                             if op.is_bang() {
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             } else {
-                                let _ignored = self.del_cell(&coord);
+                                let _ignored = self.erase(&coord);
                                 coord.y += 1;
-                                let _ignored = self.set_cell(&coord, op);
+                                let _ignored = self.write(&coord, op);
                             };
                         }
                     }
@@ -173,7 +194,7 @@ impl Snorkel {
                     // ░▀▀░░▀░▀░▀░▀░▀▀▀
                     Some(Op::Bang(frame)) => {
                         if frame != self.frame {
-                            let _ignored = self.del_cell(&coord);
+                            let _ignored = self.erase(&coord);
                         }
                     }
                     // ░█▀▀░█▀▀░█▀█
@@ -181,8 +202,9 @@ impl Snorkel {
                     // ░▀▀▀░▀▀▀░▀░▀
                     Some(Op::Gen) => {
                         let (mut offset, ops) = self.op_gen(&coord);
+                        self.lock_right(&coord, ops.len());
                         for op in ops.into_iter() {
-                            let _ignored = self.set_cell(&offset, op);
+                            let _ignored = self.write(&offset, op);
                             offset.x += 1;
                         }
                     }
@@ -192,24 +214,35 @@ impl Snorkel {
                     Some(Op::EmptyResult(ref loc)) => {
                         // if this is an orphaned empty result, delete it
                         if let None = self.get_cell(loc) {
-                            let _ignored = self.del_cell(&coord);
+                            let _ignored = self.erase(&coord);
                         }
                     }
                     // ░▀█▀░█▀█░█▀▀
                     // ░░█░░█░█░█░░
                     // ░▀▀▀░▀░▀░▀▀▀
                     Some(Op::Inc) => {
+                        self.lock_right(&coord, 1);
                         let op = self.op_inc(&coord);
                         coord.y += 1;
-                        let _ignored = self.set_cell(&coord, op);
+                        let _ignored = self.write(&coord, op);
                     }
                     // ░█░░░█▀▀░█▀▀░█▀▀
                     // ░█░░░█▀▀░▀▀█░▀▀█
                     // ░▀▀▀░▀▀▀░▀▀▀░▀▀▀
                     Some(Op::Less) => {
+                        self.lock_right(&coord, 1);
                         let op = self.op_less(&coord);
                         coord.y += 1;
-                        let _ignored = self.set_cell(&coord, op);
+                        let _ignored = self.write(&coord, op);
+                    }
+                    // ░█░░░█▀▀░█▀▄░█▀█
+                    // ░█░░░█▀▀░█▀▄░█▀▀
+                    // ░▀▀▀░▀▀▀░▀░▀░▀░░
+                    Some(Op::Lerp) => {
+                        self.lock_right(&coord, 1);
+                        let op = self.op_lerp(&coord);
+                        coord.y += 1;
+                        let _ignored = self.write(&coord, op);
                     }
                     // ░▀▀█░█░█░█▄█░█▀█░█▀▀░█▀▄
                     // ░░░█░█░█░█░█░█▀▀░█▀▀░█▀▄
@@ -217,11 +250,11 @@ impl Snorkel {
                     Some(Op::Jmp) => match self.above_of(&coord, 1) {
                         Some(op) => {
                             coord.y += 1;
-                            let _ignored = self.set_cell(&coord, op);
+                            let _ignored = self.write(&coord, op);
                         }
                         None => {
                             coord.y += 1;
-                            let _ignored = self.del_cell(&coord);
+                            let _ignored = self.erase(&coord);
                         }
                     },
                     // ░█░█░█░█░█▄█░█▀█░█▀▀░█▀▄
@@ -230,11 +263,11 @@ impl Snorkel {
                     Some(Op::Ymp) => match self.left_of(&coord, 1) {
                         Some(op) => {
                             coord.x += 1;
-                            let _ignored = self.set_cell(&coord, op);
+                            let _ignored = self.write(&coord, op);
                         }
                         None => {
                             coord.x += 1;
-                            let _ignored = self.del_cell(&coord);
+                            let _ignored = self.erase(&coord);
                         }
                     },
                     // ░█░█░█▀█░█░░░█▀▄
@@ -251,7 +284,7 @@ impl Snorkel {
                             None => Op::EmptyResult(coord.clone()),
                         };
                         coord.y += 1;
-                        let _ignored = self.set_cell(&coord, next);
+                        let _ignored = self.write(&coord, next);
                     }
                     // ░█▀▄░█▀▀░█▀█░█▀▄
                     // ░█▀▄░█▀▀░█▀█░█░█
@@ -259,7 +292,7 @@ impl Snorkel {
                     Some(Op::Read) => {
                         let op = self.op_read(&coord);
                         coord.y += 1;
-                        let _ignored = self.set_cell(&coord, op);
+                        let _ignored = self.write(&coord, op);
                     }
                     // ░█░█░█▀▄░▀█▀░▀█▀░█▀▀
                     // ░█▄█░█▀▄░░█░░░█░░█▀▀
@@ -289,12 +322,49 @@ impl Snorkel {
                     // ░█░█░█░░░█░░░░█░░█░█
                     // ░▀▀▀░▀▀▀░▀▀▀░▀▀▀░▀▀░
                     Some(Op::Uclid) => self.op_uclid(&coord),
+                    // ░█▄█░▀█▀░█▀▄░▀█▀
+                    // ░█░█░░█░░█░█░░█░
+                    // ░▀░▀░▀▀▀░▀▀░░▀▀▀
+                    Some(Op::Midi) => self.op_midi(&coord),
+                    // ░█▄█░█▀█░█▀█░█▀█
+                    // ░█░█░█░█░█░█░█░█
+                    // ░▀░▀░▀▀▀░▀░▀░▀▀▀
+                    Some(Op::Mono) => self.op_mono(&coord),
+                    // ░█▀▀░█▀▀
+                    // ░█░░░█░░
+                    // ░▀▀▀░▀▀▀
+                    Some(Op::ControlChange) => self.op_control_change(&coord),
+                    // ░█▀█░█▀▀░█▀▀
+                    // ░█░█░▀▀█░█░░
+                    // ░▀▀▀░▀▀▀░▀▀▀
+                    Some(Op::Osc) => self.op_osc(&coord),
+                    // ░█░█░█▀▄░█▀█
+                    // ░█░█░█░█░█▀▀
+                    // ░▀▀▀░▀▀░░▀░░
+                    Some(Op::Udp) => self.op_udp(&coord),
+                    // ░█▀▀░█▀▀░█░░░█▀▀
+                    // ░▀▀█░█▀▀░█░░░█▀▀
+                    // ░▀▀▀░▀▀▀░▀▀▀░▀░░
+                    Some(Op::Command) => self.op_command(&coord),
+                    // ░█▀▄░█░█░▀█▀░█▀▀
+                    // ░█▀▄░░█░░░█░░█▀▀
+                    // ░▀▀░░░▀░░░▀░░▀▀▀
+                    Some(Op::Byte) => self.op_byte(&coord),
+                    // ░█▀█░▀█▀░▀█▀░█▀▀░█░█
+                    // ░█▀▀░░█░░░█░░█░░░█▀█
+                    // ░▀░░░▀▀▀░░▀░░▀▀▀░▀░▀
+                    Some(Op::Pitch) => self.op_pitch(&coord),
                     _ => (),
                 }
             }
         }
     }
 
+    /// Takes all messages emitted by operators since the last call.
+    pub fn drain_msgs(&mut self) -> Vec<Msg> {
+        std::mem::take(&mut self.msgs)
+    }
+
     // ░█▀▀░█▀█░█▀█░█░█
     // ░█░░░█░█░█▀▀░░█░
     // ░▀▀▀░▀▀▀░▀░░░░▀░
@@ -346,6 +416,41 @@ impl Snorkel {
         UndoOp::batch(undo_ops)
     }
 
+    // ░█▀▀░▀█▀░█░░░█▀▀
+    // ░█▀▀░░█░░█░░░█▀▀
+    // ░▀░░░▀▀▀░▀▀▀░▀▀▀
+
+    /// Reads a grid in the plain text `.orca` format: one line per row,
+    /// `.` for empty cells. The grid is sized after the longest line.
+    pub fn from_orca(text: &str, frame: usize) -> Snorkel {
+        let lines: Vec<&str> = text.lines().collect();
+        let rows = lines.len();
+        let cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut snrkl = Snorkel::new(rows, cols);
+        snrkl.frame = frame;
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if let Some(op) = Op::from(c, frame) {
+                    let _ignored = snrkl.set_cell(&Coord { x, y }, op);
+                }
+            }
+        }
+        snrkl
+    }
+
+    /// Writes the grid in the plain text `.orca` format.
+    pub fn to_orca(&self) -> String {
+        let mut out = String::with_capacity(self.rows * self.cols + self.rows);
+        for y in 0..self.rows {
+            for x in 0..self.cols {
+                let glyph = self.get_cell(&Coord { x, y }).and_then(|op| op.glyph());
+                out.push(glyph.unwrap_or('.'));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
     pub fn get_cell(&self, loc: &Coord) -> Option<Op> {
         if loc.y >= self.rows || loc.x >= self.cols {
             return None;
@@ -371,27 +476,70 @@ impl Snorkel {
         old
     }
 
-    pub fn resize(&mut self, x: usize, y: usize) {
-        // first resize x (the cols) if necessary, then append
-        // new rows if necessary.
-        if x > self.cols {
-            for row in &mut self.data {
-                let diff = x.checked_sub(row.len()).unwrap_or(0);
-                if diff > 0 {
-                    row.extend(vec![None; diff]);
+    pub fn is_locked(&self, loc: &Coord) -> bool {
+        if loc.y >= self.rows || loc.x >= self.cols {
+            return false;
+        }
+        self.locks[loc.y][loc.x]
+    }
+
+    fn lock(&mut self, loc: &Coord) {
+        if loc.y >= self.rows || loc.x >= self.cols {
+            return;
+        }
+        self.locks[loc.y][loc.x] = true;
+    }
+
+    /// Locks the `count` input cells to the right of `loc`.
+    fn lock_right(&mut self, loc: &Coord, count: usize) {
+        let mut port = loc.clone();
+        for _ in 0..count {
+            port.x += 1;
+            self.lock(&port);
+        }
+    }
+
+    fn reset_locks(&mut self) {
+        self.locks.resize(self.rows, vec![]);
+        for row in &mut self.locks {
+            row.clear();
+            row.resize(self.cols, false);
+        }
+    }
+
+    /// Like `set_cell`, but also locks the cell for the rest of the frame.
+    fn write(&mut self, loc: &Coord, op: Op) -> Option<Op> {
+        self.lock(loc);
+        self.set_cell(loc, op)
+    }
+
+    /// Like `del_cell`, but also locks the cell for the rest of the frame.
+    fn erase(&mut self, loc: &Coord) -> Option<Op> {
+        self.lock(loc);
+        self.del_cell(loc)
+    }
+
+    /// Crops or pads the grid to `cols` x `rows`. Returns how to undo it,
+    /// i.e. the old size and the cells that were cropped.
+    pub fn resize(&mut self, cols: usize, rows: usize) -> UndoOp {
+        let mut cropped = vec![];
+        for (y, row) in self.data.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if (x >= cols || y >= rows) && cell.is_some() {
+                    cropped.push((Coord { x, y }, cell.clone()));
                 }
             }
         }
-        self.cols = x;
+        let undo = UndoOp::resize(self.cols, self.rows, cropped);
 
-        // now add new rows if requested
-        if y > self.rows {
-            let diff = y.checked_sub(self.data.len()).unwrap_or(0);
-            for _ in 0..diff {
-                self.data.push(vec![None; self.cols])
-            }
+        self.data.resize(rows, vec![None; cols]);
+        for row in &mut self.data {
+            row.resize(cols, None);
         }
-        self.rows = y;
+        self.cols = cols;
+        self.rows = rows;
+        self.reset_locks();
+        undo
     }
 
     // ░█▀█░█▀█░█▀▀
@@ -595,6 +743,30 @@ impl Snorkel {
         }
     }
 
+    pub fn op_lerp(&self, loc: &Coord) -> Op {
+        let rate = self
+            .left_of(loc, 1)
+            .and_then(|op| op.extract_num())
+            .unwrap_or(1) as isize;
+        let target = self
+            .right_of(loc, 1)
+            .and_then(|op| op.extract_num())
+            .unwrap_or(0) as isize;
+        let current = self
+            .below_of(loc, 1)
+            .and_then(|op| op.extract_num())
+            .unwrap_or(0) as isize;
+        // step towards the target by `rate`, but never overshoot it
+        let step = if current <= target - rate {
+            rate
+        } else if current >= target + rate {
+            -rate
+        } else {
+            target - current
+        };
+        Op::Result(Op::as_value_char((current + step) as usize, false))
+    }
+
     pub fn op_gen(&self, loc: &Coord) -> (Coord, Vec<Op>) {
         let mut start = loc.clone();
         // enforce that this should by default be a line under current
@@ -637,7 +809,7 @@ impl Snorkel {
         return (start, ops);
     }
 
-    pub fn op_read(&self, loc: &Coord) -> Op {
+    pub fn op_read(&mut self, loc: &Coord) -> Op {
         let x = self
             .left_of(loc, 2)
             .and_then(|op| op.extract_num())
@@ -649,6 +821,7 @@ impl Snorkel {
         let mut source = loc.clone();
         source.x += cmp::max(x, 1);
         source.y += y;
+        self.lock(&source);
         self.get_cell(&source)
             .map(|op| match op {
                 Op::Val(ref c) => Op::Result(*c),
@@ -658,6 +831,7 @@ impl Snorkel {
     }
 
     pub fn op_write(&mut self, loc: &Coord) {
+        self.lock_right(loc, 1);
         let mut target = loc.clone();
         target.y += 1;
         let op = self
@@ -677,7 +851,7 @@ impl Snorkel {
             .unwrap_or(0);
         target.x += x;
         target.y += y;
-        let _ingored = self.set_cell(&target, op);
+        let _ingored = self.write(&target, op);
     }
 
     pub fn op_push(&mut self, loc: &Coord) {
@@ -686,6 +860,7 @@ impl Snorkel {
             .and_then(|op| op.extract_num())
             .and_then(|n| if n > 0 { Some(n) } else { None });
         if let Some(len) = len {
+            self.lock_right(loc, 1);
             let x = self
                 .left_of(loc, 2)
                 .and_then(|op| op.extract_num())
@@ -701,7 +876,7 @@ impl Snorkel {
             let mut target = loc.clone();
             target.y += 1;
             target.x += x;
-            let _ignored = self.set_cell(&target, val);
+            let _ignored = self.write(&target, val);
         }
     }
 
@@ -711,6 +886,7 @@ impl Snorkel {
             .and_then(|op| op.extract_num())
             .map(|n| cmp::max(n, 1))
             .unwrap_or(1);
+        self.lock_right(loc, len);
         let mut target = loc.clone();
         target.y += 1;
         for offset in 1..len + 1 {
@@ -726,11 +902,12 @@ impl Snorkel {
                 })
                 .unwrap_or_else(|| Op::EmptyResult(loc.clone()));
             target.x += 1;
-            let _ignored = self.set_cell(&target, op);
+            let _ignored = self.write(&target, op);
         }
     }
 
     pub fn op_var(&mut self, loc: &Coord) {
+        self.lock_right(loc, 1);
         let left = self.left_of(&loc, 1).and_then(|op| match op {
             Op::Val(ref c) => Some(*c),
             Op::Result(ref c) => Some(*c),
@@ -756,10 +933,10 @@ impl Snorkel {
         below.y += 1;
         match op {
             Some(op) => {
-                let _ignore = self.set_cell(&below, op);
+                let _ignore = self.write(&below, op);
             }
             None => {
-                let _ignore = self.del_cell(&below);
+                let _ignore = self.erase(&below);
             }
         }
     }
@@ -779,15 +956,17 @@ impl Snorkel {
         };
 
         for i in (0..count).rev() {
-            if let Some(op) = self.get_cell(&Coord {
+            let src = Coord {
                 x: loc.x + 1 + x_offset + i,
                 y: loc.y + y_offset,
-            }) {
+            };
+            self.lock(&src);
+            if let Some(op) = self.get_cell(&src) {
                 let dest = Coord {
                     x: loc.x.checked_sub(count).unwrap_or(0) + (i + 1),
                     y: loc.y + 1,
                 };
-                self.set_cell(&dest, op);
+                self.write(&dest, op);
             };
         }
     }
@@ -800,6 +979,7 @@ impl Snorkel {
             .map(|n| n + 1);
         match (key, len) {
             (Some(key), Some(len)) => {
+                self.lock_right(loc, len);
                 let x_off = key % len;
                 let src = Coord {
                     x: loc.x + 1 + x_off,
@@ -810,7 +990,7 @@ impl Snorkel {
                         x: loc.x,
                         y: loc.y + 1,
                     };
-                    self.set_cell(&dest, op);
+                    self.write(&dest, op);
                 }
             }
             _ => return,
@@ -825,25 +1005,200 @@ impl Snorkel {
         let max = self.right_of(&loc, 1).and_then(|op| op.extract_num());
         match (step, max) {
             (Some(step), Some(max)) => {
+                self.lock_right(loc, 1);
                 let curr = self.frame % step;
                 let dest = Coord {
                     x: loc.x,
                     y: loc.y + 1,
                 };
                 if curr == 0 && curr < max {
-                    self.set_cell(&dest, Op::Bang(self.frame));
+                    self.write(&dest, Op::Bang(self.frame));
                 } else {
-                    self.del_cell(&dest);
+                    self.erase(&dest);
                 }
             }
             _ => return,
         }
     }
 
+    fn op_midi(&mut self, loc: &Coord) {
+        if let Some((channel, note, velocity, length)) = self.note_ports(loc) {
+            self.msgs.push(Msg::Note {
+                channel,
+                note,
+                velocity,
+                length,
+            });
+        }
+    }
+
+    fn op_mono(&mut self, loc: &Coord) {
+        if let Some((channel, note, velocity, length)) = self.note_ports(loc) {
+            self.msgs.push(Msg::MonoNote {
+                channel,
+                note,
+                velocity,
+                length,
+            });
+        }
+    }
+
+    /// Reads the `channel octave note velocity length` ports shared by the
+    /// note operators. Returns `None` unless banged with a playable note.
+    fn note_ports(&mut self, loc: &Coord) -> Option<(u8, u8, u8, usize)> {
+        self.lock_right(loc, 5);
+        if !self.is_banged(loc) {
+            return None;
+        }
+        let channel = match self.right_of(loc, 1).and_then(|op| op.extract_num()) {
+            Some(c) if c < 16 => c as u8,
+            _ => return None,
+        };
+        let octave = self.right_of(loc, 2).and_then(|op| op.extract_num())?;
+        let note = self
+            .right_of(loc, 3)
+            .and_then(|op| op.glyph())
+            .and_then(|c| midi::note_number(octave, c))?;
+        // velocity goes from 0 to g (16), mapped onto 0..127
+        let velocity = self
+            .right_of(loc, 4)
+            .and_then(|op| op.extract_num())
+            .map(|v| cmp::min(v, 16))
+            .unwrap_or(15);
+        let length = self
+            .right_of(loc, 5)
+            .and_then(|op| op.extract_num())
+            .map(|l| cmp::max(l, 1))
+            .unwrap_or(1);
+        Some((channel, note, (velocity * 127 / 16) as u8, length))
+    }
+
+    fn op_control_change(&mut self, loc: &Coord) {
+        self.lock_right(loc, 3);
+        if !self.is_banged(loc) {
+            return;
+        }
+        let channel = match self.right_of(loc, 1).and_then(|op| op.extract_num()) {
+            Some(c) if c < 16 => c as u8,
+            _ => return,
+        };
+        let knob = match self.right_of(loc, 2).and_then(|op| op.extract_num()) {
+            Some(k) => k as u8,
+            None => return,
+        };
+        let value = match self.right_of(loc, 3).and_then(|op| op.extract_num()) {
+            Some(v) => v,
+            None => return,
+        };
+        // map base 36 onto 0..127, so z is always full scale
+        let value = (value * 127).div_ceil(35) as u8;
+        self.msgs.push(Msg::ControlChange {
+            channel,
+            knob,
+            value,
+        });
+    }
+
+    fn op_osc(&mut self, loc: &Coord) {
+        self.lock_right(loc, 1);
+        let path = self.right_of(loc, 1).and_then(|op| op.glyph());
+        let values = self.read_text(loc, 2);
+        if !self.is_banged(loc) {
+            return;
+        }
+        if let Some(path) = path {
+            let args = values
+                .chars()
+                .map(|c| Op::as_num(c).unwrap_or(0) as i32)
+                .collect();
+            self.msgs.push(Msg::Osc {
+                path: format!("/{}", path),
+                args,
+            });
+        }
+    }
+
+    fn op_udp(&mut self, loc: &Coord) {
+        let text = self.read_text(loc, 1);
+        if self.is_banged(loc) && !text.is_empty() {
+            self.msgs.push(Msg::Udp(text));
+        }
+    }
+
+    fn op_command(&mut self, loc: &Coord) {
+        let text = self.read_text(loc, 1);
+        if !self.is_banged(loc) || text.is_empty() {
+            return;
+        }
+        match Command::parse(&text) {
+            Some(cmd) => self.msgs.push(Msg::Command(cmd)),
+            None => log::warn!("unknown command: {}", text),
+        }
+    }
+
+    fn op_byte(&mut self, loc: &Coord) {
+        self.lock_right(loc, 2);
+        if !self.is_banged(loc) {
+            return;
+        }
+        let hex = |op: Option<Op>| op.and_then(|op| op.glyph()).and_then(|c| c.to_digit(16));
+        let high = hex(self.right_of(loc, 1));
+        let low = hex(self.right_of(loc, 2));
+        if let (Some(high), Some(low)) = (high, low) {
+            self.msgs.push(Msg::Byte((high * 16 + low) as u8));
+        }
+    }
+
+    fn op_pitch(&mut self, loc: &Coord) {
+        self.lock_right(loc, 2);
+        if !self.is_banged(loc) {
+            return;
+        }
+        let note = self
+            .right_of(loc, 1)
+            .and_then(|op| op.extract_num())
+            .and_then(|octave| {
+                self.right_of(loc, 2)
+                    .and_then(|op| op.glyph())
+                    .and_then(|c| midi::note_number(octave, c))
+            });
+        if let Some(note) = note {
+            self.msgs.push(Msg::Byte(note));
+        }
+    }
+
     // ░█░█░▀█▀░▀█▀░█░░
     // ░█░█░░█░░░█░░█░░
     // ░▀▀▀░░▀░░▀▀▀░▀▀▀
 
+    /// Reads and locks the glyphs right of `loc`, starting `offset` cells
+    /// away and stopping at the first empty cell or the edge of the grid.
+    fn read_text(&mut self, loc: &Coord, offset: usize) -> String {
+        let mut text = String::new();
+        let mut cursor = Coord {
+            x: loc.x + offset,
+            y: loc.y,
+        };
+        while let Some(c) = self.get_cell(&cursor).and_then(|op| op.glyph()) {
+            self.lock(&cursor);
+            text.push(c);
+            cursor.x += 1;
+        }
+        text
+    }
+
+    /// Whether any of the four direct neighbours of `loc` is a bang.
+    fn is_banged(&self, loc: &Coord) -> bool {
+        [
+            self.left_of(loc, 1),
+            self.right_of(loc, 1),
+            self.above_of(loc, 1),
+            self.below_of(loc, 1),
+        ]
+        .iter()
+        .any(|op| matches!(op, Some(Op::Bang(_))))
+    }
+
     fn left_of(&self, loc: &Coord, offset: usize) -> Option<Op> {
         let left_x = loc.x.checked_sub(offset);
         if left_x.is_none() {
@@ -936,7 +1291,7 @@ impl Snorkel {
 
 #[cfg(test)]
 mod tick_tests {
-    use crate::{op::Op, util::Coord};
+    use crate::{command::Command, msg::Msg, op::Op, util::Coord};
 
     use super::Snorkel;
 
@@ -1064,6 +1419,436 @@ mod tick_tests {
             snrkl.get_cell(&Coord { x: 1, y: 1 })
         );
     }
+
+    // Lerp
+
+    #[test]
+    fn lerp_should_step_towards_target_by_rate() {
+        let mut snrkl = Snorkel::new(5, 5);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Val('2'));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Lerp);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('5'));
+        assert_eq!(None, snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('2')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('4')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        // the last step is shortened so the target is not overshot
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('5')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('5')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn lerp_should_step_down_towards_lower_target() {
+        let mut snrkl = Snorkel::new(5, 5);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Val('3'));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Lerp);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('1'));
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Val('9'));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('6')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('3')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('1')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn incomplete_lerp_should_use_default_rate_and_target() {
+        let mut snrkl = Snorkel::new(5, 5);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Lerp);
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Val('2'));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('1')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('0')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('0')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn lerp_with_zero_rate_should_hold_value() {
+        let mut snrkl = Snorkel::new(5, 5);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Val('0'));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Lerp);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('z'));
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Val('4'));
+        snrkl.tick();
+        assert_eq!(Some(Op::Result('4')), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+    }
+
+    // Midi
+
+    #[test]
+    fn banged_midi_should_emit_note() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Midi);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('0')); // channel
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('3')); // octave
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Clock); // note C
+        snrkl.tick();
+        let expected = vec![Msg::Note {
+            channel: 0,
+            note: 60,
+            velocity: 119,
+            length: 1,
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+        assert!(snrkl.drain_msgs().is_empty());
+        // the note glyph is an input and must not run as a clock
+        assert_eq!(None, snrkl.get_cell(&Coord { x: 4, y: 1 }));
+    }
+
+    #[test]
+    fn midi_should_read_velocity_and_length() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Midi);
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('1')); // channel
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('4')); // octave
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('c')); // note c#
+        snrkl.set_cell(&Coord { x: 5, y: 0 }, Op::Val('g')); // velocity
+        snrkl.set_cell(&Coord { x: 6, y: 0 }, Op::Val('4')); // length
+        snrkl.tick();
+        let expected = vec![Msg::Note {
+            channel: 1,
+            note: 73,
+            velocity: 127,
+            length: 4,
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+    }
+
+    #[test]
+    fn midi_without_bang_should_not_emit_note() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Midi);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('0'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('3'));
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('c'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    #[test]
+    fn incomplete_midi_should_not_emit_note() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Midi);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('g')); // channel out of range
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('3'));
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('c'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('0'));
+        snrkl.del_cell(&Coord { x: 4, y: 0 });
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    #[test]
+    fn banged_mono_should_emit_mono_note() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Mono);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('3')); // channel
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('2')); // octave
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Delay); // note D
+        snrkl.set_cell(&Coord { x: 5, y: 0 }, Op::Val('8')); // velocity
+        snrkl.set_cell(&Coord { x: 6, y: 0 }, Op::Val('2')); // length
+        snrkl.tick();
+        let expected = vec![Msg::MonoNote {
+            channel: 3,
+            note: 50,
+            velocity: 63,
+            length: 2,
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+    }
+
+    // Control change
+
+    #[test]
+    fn banged_control_change_should_emit_scaled_value() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::ControlChange);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('2')); // channel
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('a')); // knob
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('z')); // value
+        snrkl.tick();
+        let expected = vec![Msg::ControlChange {
+            channel: 2,
+            knob: 10,
+            value: 127,
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('h'));
+        snrkl.tick();
+        let expected = vec![Msg::ControlChange {
+            channel: 2,
+            knob: 10,
+            value: 62,
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('0'));
+        snrkl.tick();
+        let expected = vec![Msg::ControlChange {
+            channel: 2,
+            knob: 10,
+            value: 0,
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+    }
+
+    #[test]
+    fn control_change_should_need_bang_and_all_inputs() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::ControlChange);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('0'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('1'));
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('2'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Bang(0));
+        snrkl.del_cell(&Coord { x: 4, y: 0 });
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    // Osc
+
+    #[test]
+    fn banged_osc_should_emit_message() {
+        let mut snrkl = Snorkel::new(3, 10);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Osc);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('a')); // path
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('1'));
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Val('z'));
+        snrkl.set_cell(&Coord { x: 5, y: 0 }, Op::Add);
+        snrkl.set_cell(&Coord { x: 7, y: 0 }, Op::Val('3'));
+        snrkl.tick();
+        let expected = vec![Msg::Osc {
+            path: "/a".to_string(),
+            args: vec![1, 35, 10],
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+        // values are inputs, so the add operator must not run
+        assert_eq!(None, snrkl.get_cell(&Coord { x: 5, y: 1 }));
+    }
+
+    #[test]
+    fn osc_should_need_bang_and_path() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Osc);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('a'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.tick();
+        let expected = vec![Msg::Osc {
+            path: "/a".to_string(),
+            args: vec![],
+        }];
+        assert_eq!(expected, snrkl.drain_msgs());
+
+        snrkl.del_cell(&Coord { x: 2, y: 0 });
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    // Udp
+
+    #[test]
+    fn banged_udp_should_emit_string() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Udp);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('h'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('1'));
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Comment);
+        snrkl.set_cell(&Coord { x: 6, y: 0 }, Op::Val('x'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Bang(0));
+        snrkl.tick();
+        assert_eq!(vec![Msg::Udp("h1#".to_string())], snrkl.drain_msgs());
+    }
+
+    #[test]
+    fn udp_without_text_should_not_emit() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Udp);
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    // Command
+
+    #[test]
+    fn banged_command_should_emit_parsed_command() {
+        let mut snrkl = Snorkel::new(3, 10);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Command);
+        for (x, c) in "bpm:140".chars().enumerate() {
+            let op = Op::from(c, 0).unwrap();
+            snrkl.set_cell(&Coord { x: x + 2, y: 0 }, op);
+        }
+        snrkl.tick();
+        assert_eq!(vec![Msg::Command(Command::Bpm(140))], snrkl.drain_msgs());
+    }
+
+    #[test]
+    fn unknown_command_should_not_emit() {
+        let mut snrkl = Snorkel::new(3, 10);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Command);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('x'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    // Byte
+
+    #[test]
+    fn banged_byte_should_emit_hex_value() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Byte);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('f'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('a'));
+        snrkl.tick();
+        assert_eq!(vec![Msg::Byte(0xFA)], snrkl.drain_msgs());
+
+        // upper case hex digits are operators, but still read as glyphs
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('0'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Clock);
+        snrkl.tick();
+        assert_eq!(vec![Msg::Byte(0x0C)], snrkl.drain_msgs());
+    }
+
+    #[test]
+    fn byte_should_need_bang_and_two_hex_digits() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Byte);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('f'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('8'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('g'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.del_cell(&Coord { x: 3, y: 0 });
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    // Pitch
+
+    #[test]
+    fn banged_pitch_should_emit_note_number_byte() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Pitch);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('3')); // octave
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Add); // note A
+        snrkl.tick();
+        assert_eq!(vec![Msg::Byte(69)], snrkl.drain_msgs());
+    }
+
+    #[test]
+    fn pitch_should_need_bang_octave_and_note() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Pitch);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('3'));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('c'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('1'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+
+        snrkl.del_cell(&Coord { x: 2, y: 0 });
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Val('c'));
+        snrkl.tick();
+        assert!(snrkl.drain_msgs().is_empty());
+    }
+
+    // Locking
+
+    #[test]
+    fn operator_inputs_should_be_locked_for_the_frame() {
+        let mut snrkl = Snorkel::new(2, 5);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Val('1'));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Add);
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::East(0));
+        snrkl.frame = 1;
+        snrkl.tick();
+        assert!(snrkl.is_locked(&Coord { x: 2, y: 0 }));
+        assert_eq!(Some(Op::East(0)), snrkl.get_cell(&Coord { x: 2, y: 0 }));
+        assert_eq!(None, snrkl.get_cell(&Coord { x: 3, y: 0 }));
+    }
+
+    #[test]
+    fn written_cells_should_not_be_evaluated_again_in_same_frame() {
+        let mut snrkl = Snorkel::new(3, 8);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Val('0')); // x
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Val('0')); // y
+        snrkl.set_cell(&Coord { x: 2, y: 0 }, Op::Val('0')); // len
+        snrkl.set_cell(&Coord { x: 3, y: 0 }, Op::Gen);
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::East(0));
+        snrkl.frame = 1;
+        snrkl.tick();
+        let rendered = snrkl.render();
+        let expected = r#"
+000GE···
+···E····
+········
+"#;
+        assert_eq!(expected.trim_start(), rendered);
+
+        // without the generator both operators are free to move again
+        snrkl.frame = 2;
+        snrkl.del_cell(&Coord { x: 3, y: 0 });
+        snrkl.tick();
+        let rendered = snrkl.render();
+        let expected = r#"
+000··E··
+····E···
+········
+"#;
+        assert_eq!(expected.trim_start(), rendered);
+    }
+
+    #[test]
+    fn locks_should_be_released_on_next_tick() {
+        let mut snrkl = Snorkel::new(3, 3);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::South(0));
+        snrkl.frame = 1;
+        snrkl.tick();
+        assert_eq!(Some(Op::South(1)), snrkl.get_cell(&Coord { x: 0, y: 1 }));
+        assert!(snrkl.is_locked(&Coord { x: 0, y: 1 }));
+        snrkl.frame = 2;
+        snrkl.tick();
+        assert_eq!(None, snrkl.get_cell(&Coord { x: 0, y: 1 }));
+        assert_eq!(Some(Op::South(2)), snrkl.get_cell(&Coord { x: 0, y: 2 }));
+    }
 }
 
 // ░█▀▀░█▀█░█▀█░█░█░░░█░█▀█░█▀█░█▀▀░▀█▀░█▀▀
@@ -1153,9 +1938,56 @@ mod copy_paste_tests {
     }
 }
 
+// ░█▀▀░▀█▀░█░░░█▀▀
+// ░█▀▀░░█░░█░░░█▀▀
+// ░▀░░░▀▀▀░▀▀▀░▀▀▀ tests
+
+#[cfg(test)]
+mod file_tests {
+    use crate::{op::Op, snorkel::Snorkel, util::Coord};
+
+    #[test]
+    fn should_read_orca_text() {
+        let text = "..1A2\n.*\n#..:03C.\n";
+        let snrkl = Snorkel::from_orca(text, 0);
+        assert_eq!(3, snrkl.rows);
+        assert_eq!(8, snrkl.cols);
+        assert_eq!(Some(Op::Val('1')), snrkl.get_cell(&Coord { x: 2, y: 0 }));
+        assert_eq!(Some(Op::Add), snrkl.get_cell(&Coord { x: 3, y: 0 }));
+        assert_eq!(Some(Op::Bang(0)), snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        assert_eq!(None, snrkl.get_cell(&Coord { x: 4, y: 1 }));
+        assert_eq!(Some(Op::Midi), snrkl.get_cell(&Coord { x: 3, y: 2 }));
+        assert_eq!(Some(Op::Clock), snrkl.get_cell(&Coord { x: 6, y: 2 }));
+    }
+
+    #[test]
+    fn should_write_orca_text() {
+        let mut snrkl = Snorkel::new(2, 4);
+        snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Val('1'));
+        snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Add);
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Result('3'));
+        snrkl.set_cell(&Coord { x: 2, y: 1 }, Op::EmptyResult(Coord { x: 2, y: 0 }));
+        snrkl.set_cell(&Coord { x: 3, y: 1 }, Op::East(0));
+        assert_eq!("1A..\n.3.E\n", snrkl.to_orca());
+    }
+
+    #[test]
+    fn should_round_trip_orca_text() {
+        let text = "#.snorkel.#\n.D4........\n..*.:03C...\n...........\n";
+        let snrkl = Snorkel::from_orca(text, 0);
+        assert_eq!(text, snrkl.to_orca());
+    }
+
+    #[test]
+    fn should_handle_windows_line_endings() {
+        let snrkl = Snorkel::from_orca("1.\r\n.2\r\n", 0);
+        assert_eq!("1.\n.2\n", snrkl.to_orca());
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{op::Op, util::Coord};
+    use crate::{op::Op, state::UndoOp, util::Coord};
 
     use super::Snorkel;
 
@@ -1256,6 +2088,7 @@ mod tests {
 
         assert_eq!(expected.trim_start(), rendered);
 
+        // cropped cells are gone for good
         snrkl.resize(10, 10);
         let rendered = snrkl.render();
         let expected = r#"
@@ -1268,12 +2101,39 @@ mod tests {
 ··········
 ··········
 ··········
-·········U
+··········
 "#;
 
         assert_eq!(expected.trim_start(), rendered);
     }
 
+    #[test]
+    fn resize_should_return_cropped_cells() {
+        let mut snrkl = Snorkel::new(3, 5);
+        snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Add);
+        snrkl.set_cell(&Coord { x: 4, y: 0 }, Op::Clock);
+        snrkl.set_cell(&Coord { x: 0, y: 2 }, Op::Uclid);
+        match snrkl.resize(2, 6) {
+            UndoOp::Resize {
+                cols, rows, ops, ..
+            } => {
+                assert_eq!((5, 3), (cols, rows));
+                assert_eq!(vec![(Coord { x: 4, y: 0 }, Some(Op::Clock))], ops);
+            }
+            undo => panic!("unexpected {:?}", undo),
+        }
+        assert_eq!((2, 6), (snrkl.cols, snrkl.rows));
+        let expected = r#"
+··
+·A
+U·
+··
+··
+··
+"#;
+        assert_eq!(expected.trim_start(), snrkl.render());
+    }
+
     #[test]
     fn op_query() {
         let mut snrkl = Snorkel::new(8, 8);
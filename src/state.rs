@@ -1,15 +1,27 @@
+use crate::cmdline::{CmdLine, EditorCommand};
+use crate::command::Command;
 use crate::config::Config;
+use crate::jitter::Jitter;
+use crate::linux::RtPriority;
 use crate::mode::{
-    InsertKeymap, InsertModeCommand, Movement, NormalKeymap, NormalModeCommand, ReplaceKeymap,
-    ReplaceModeCommand, SelectKeymap, SelectModeCommand,
+    CmdlineKeymap, CmdlineModeCommand, InsertKeymap, InsertModeCommand, Movement, NormalKeymap,
+    NormalModeCommand, PickerKeymap, PickerModeCommand, ReplaceKeymap, ReplaceModeCommand,
+    SelectKeymap, SelectModeCommand,
 };
+use crate::msg::Msg;
 use crate::op::Op;
+use crate::output::{MidiDevices, Packet};
 use crate::snorkel::Snorkel;
-use crate::util::{Coord, Selection};
+use crate::transport::MAX_SWING;
+use crate::util::{Coord, Selection, Viewport};
 use crossterm::event::{KeyCode, KeyEvent};
 use std::cmp;
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
+use std::{fs, io};
 
 #[derive(Default, Debug, Eq, PartialEq, PartialOrd, Ord)]
 pub enum EditorState {
@@ -18,6 +30,8 @@ pub enum EditorState {
     Normal,
     Replace,
     Select,
+    Command,
+    PickDevice,
     QuitRequested,
     QuitConfirmed,
 }
@@ -30,6 +44,8 @@ impl Display for EditorState {
             Normal => f.write_str("normal"),
             Replace => f.write_str("replace"),
             Select => f.write_str("select"),
+            Command => f.write_str("command"),
+            PickDevice => f.write_str("device"),
             QuitRequested => f.write_str("quit?"),
             QuitConfirmed => f.write_str("bye"),
         }
@@ -47,6 +63,13 @@ pub enum UndoOp {
         ts: Instant,
         ops: Vec<(Coord, Option<Op>)>,
     },
+    /// Resize the grid to `cols` x `rows`, then restore the cropped `ops`.
+    Resize {
+        ts: Instant,
+        cols: usize,
+        rows: usize,
+        ops: Vec<(Coord, Option<Op>)>,
+    },
 }
 
 impl UndoOp {
@@ -64,6 +87,15 @@ impl UndoOp {
             ops,
         }
     }
+
+    pub fn resize(cols: usize, rows: usize, ops: Vec<(Coord, Option<Op>)>) -> Self {
+        UndoOp::Resize {
+            ts: Instant::now(),
+            cols,
+            rows,
+            ops,
+        }
+    }
 }
 
 pub struct AppState {
@@ -76,6 +108,27 @@ pub struct AppState {
     pub snrkl: Snorkel,
     pub config: Config,
     pub show_logs: bool,
+    pub show_jitter: bool,
+    pub jitter: Jitter,
+    pub output: Option<Sender<Packet>>,
+    pub bpm: usize,
+    pub playing: bool,
+    /// How much every other frame is delayed, in percent of a frame.
+    pub swing: u8,
+    /// Whether the transport thread got real time priority, once it tried.
+    pub rt_priority: Option<RtPriority>,
+    /// Whether the tempo follows an external clock.
+    pub synced: bool,
+    pub file: Option<PathBuf>,
+    /// The MIDI outputs, as last seen by the output thread.
+    pub midi_devices: Arc<Mutex<MidiDevices>>,
+    /// The highlighted entry of the device picker.
+    pub device_cursor: usize,
+    pub cmdline: CmdLine,
+    /// Whether the grid grows along with the terminal.
+    pub fit_terminal: bool,
+    /// The part of the grid on screen, updated while drawing.
+    pub viewport: Viewport,
 }
 
 impl AppState {
@@ -90,6 +143,20 @@ impl AppState {
             snrkl: Snorkel::new(rows, cols),
             undo_steps: Vec::new(),
             show_logs: true,
+            show_jitter: false,
+            jitter: Jitter::default(),
+            output: None,
+            bpm: 120,
+            playing: false,
+            synced: false,
+            rt_priority: None,
+            swing: 0,
+            file: None,
+            midi_devices: Arc::default(),
+            device_cursor: 0,
+            cmdline: CmdLine::default(),
+            fit_terminal: false,
+            viewport: Viewport::default(),
         }
     }
 
@@ -102,15 +169,27 @@ impl AppState {
                         EnterInsertMode => self.edit_state = EditorState::Insert,
                         EnterReplaceMode => self.edit_state = EditorState::Replace,
                         EnterSelectMode => self.edit_state = EditorState::Select,
-                        NextFrame => {
-                            self.snrkl.frame += 1;
-                            self.tick();
-                        }
+                        EnterCommandMode => self.edit_state = EditorState::Command,
+                        NextFrame => self.next_frame(),
                         ResetFrame => {
                             self.snrkl.frame = 0;
                             self.tick();
                         }
+                        TogglePlay if self.playing => self.pause(),
+                        TogglePlay => self.play(),
+                        Stop => self.stop(),
+                        Swing(delta) => self.adjust_swing(delta),
                         ToggleLogs => self.show_logs = !self.show_logs,
+                        ToggleJitter => self.show_jitter = !self.show_jitter,
+                        PickDevice => self.pick_device(),
+                        Save => match self.file.clone() {
+                            Some(path) => {
+                                if let Err(err) = self.save(&path) {
+                                    log::error!("could not save {}: {}", path.display(), err);
+                                }
+                            }
+                            None => log::warn!("no file to save to"),
+                        },
                         Move(movement) => self.move_cursor(movement),
                         Delete => {
                             let old = self.snrkl.del_cell(&self.cursor);
@@ -123,55 +202,15 @@ impl AppState {
                             }
                         }
                         Undo => {
-                            // TODO: we could make this into a method maybe to prevent
-                            // code duplication?
-                            use UndoOp::*;
-                            match self.undo_steps.pop() {
-                                Some(Step { ts: _, loc, op }) => {
-                                    let old = match op {
-                                        Some(op) => self.snrkl.set_cell(&loc, op),
-                                        None => self.snrkl.del_cell(&loc),
-                                    };
-                                    self.redo_steps.push(UndoOp::step(&loc, old));
-                                }
-                                Some(Batch { ts: _, ops }) => {
-                                    let mut old_ops = vec![];
-                                    for (loc, op) in ops {
-                                        let old = match op {
-                                            Some(op) => self.snrkl.set_cell(&loc, op),
-                                            None => self.snrkl.del_cell(&loc),
-                                        };
-                                        old_ops.push((loc, old));
-                                    }
-                                    self.redo_steps.push(UndoOp::batch(old_ops));
-                                }
-                                None => (),
+                            if let Some(step) = self.undo_steps.pop() {
+                                let redo = self.revert(step);
+                                self.redo_steps.push(redo);
                             }
                         }
                         Redo => {
-                            // TODO: we could make this into a method maybe to prevent
-                            // code duplication?
-                            use UndoOp::*;
-                            match self.redo_steps.pop() {
-                                Some(Step { ts: _, loc, op }) => {
-                                    let old = match op {
-                                        Some(op) => self.snrkl.set_cell(&loc, op),
-                                        None => self.snrkl.del_cell(&loc),
-                                    };
-                                    self.undo_steps.push(UndoOp::step(&loc, old));
-                                }
-                                Some(Batch { ts: _, ops }) => {
-                                    let mut old_ops = vec![];
-                                    for (loc, op) in ops {
-                                        let old = match op {
-                                            Some(op) => self.snrkl.set_cell(&loc, op),
-                                            None => self.snrkl.del_cell(&loc),
-                                        };
-                                        old_ops.push((loc, old));
-                                    }
-                                    self.undo_steps.push(UndoOp::batch(old_ops));
-                                }
-                                None => (),
+                            if let Some(step) = self.redo_steps.pop() {
+                                let undo = self.revert(step);
+                                self.undo_steps.push(undo);
                             }
                         }
                         Exit => self.edit_state = EditorState::QuitRequested,
@@ -233,6 +272,61 @@ impl AppState {
                     }
                 }
             }
+            EditorState::Command => {
+                use CmdlineModeCommand::*;
+                if let Some(cmd) = CmdlineKeymap::parse_key(key) {
+                    match cmd {
+                        Char(c) => self.cmdline.push(c),
+                        Backspace if self.cmdline.input.is_empty() => {
+                            self.edit_state = EditorState::default()
+                        }
+                        Backspace => self.cmdline.backspace(),
+                        Complete => self.cmdline.complete(),
+                        Prev => self.cmdline.prev(),
+                        Next => self.cmdline.next(),
+                        Run => {
+                            self.edit_state = EditorState::default();
+                            let line = self.cmdline.take();
+                            match EditorCommand::parse(&line) {
+                                Some(cmd) => self.run(cmd),
+                                None if line.trim().is_empty() => (),
+                                None => log::warn!("not a command: {}", line),
+                            }
+                        }
+                        Exit => {
+                            self.cmdline.cancel();
+                            self.edit_state = EditorState::default();
+                        }
+                    }
+                }
+            }
+            EditorState::PickDevice => {
+                use PickerModeCommand::*;
+                if let Some(cmd) = PickerKeymap::parse_key(key) {
+                    let count = self.midi_devices.lock().unwrap().outputs.len();
+                    match cmd {
+                        Up => self.device_cursor = self.device_cursor.saturating_sub(1),
+                        Down => {
+                            self.device_cursor =
+                                cmp::min(self.device_cursor + 1, count.saturating_sub(1))
+                        }
+                        Choose => {
+                            let name = self
+                                .midi_devices
+                                .lock()
+                                .unwrap()
+                                .outputs
+                                .get(self.device_cursor)
+                                .cloned();
+                            if let Some(name) = name {
+                                self.send(Packet::SelectDevice(name));
+                            }
+                            self.edit_state = EditorState::default();
+                        }
+                        Exit => self.edit_state = EditorState::default(),
+                    }
+                }
+            }
             EditorState::QuitRequested => match key.code {
                 KeyCode::Esc => self.edit_state = EditorState::Normal,
                 KeyCode::Enter => self.edit_state = EditorState::QuitConfirmed,
@@ -242,8 +336,249 @@ impl AppState {
         }
     }
 
+    /// Opens the device picker on the current MIDI output, and has the
+    /// output thread list the devices again.
+    pub fn pick_device(&mut self) {
+        self.send(Packet::Rescan);
+        let devices = self.midi_devices.lock().unwrap();
+        self.device_cursor = devices
+            .current
+            .as_ref()
+            .and_then(|current| devices.outputs.iter().position(|name| name == current))
+            .unwrap_or(0);
+        drop(devices);
+        self.edit_state = EditorState::PickDevice;
+    }
+
+    /// Advances to the next frame and evaluates it.
+    pub fn next_frame(&mut self) {
+        self.snrkl.frame += 1;
+        self.tick();
+    }
+
     pub fn tick(&mut self) {
-        self.snrkl.tick()
+        self.snrkl.tick();
+        let mut msgs = vec![];
+        for msg in self.snrkl.drain_msgs() {
+            match msg {
+                Msg::Command(cmd) => self.command(cmd),
+                msg => msgs.push(msg),
+            }
+        }
+        self.send(Packet::Frame(msgs));
+    }
+
+    /// Starts playing, from the top when stopped or from where it was paused.
+    pub fn play(&mut self) {
+        if self.playing {
+            return;
+        }
+        self.playing = true;
+        let msg = if self.snrkl.frame == 0 {
+            Msg::Start
+        } else {
+            Msg::Continue
+        };
+        self.send(Packet::Transport(msg));
+    }
+
+    /// Holds the transport on the current frame.
+    pub fn pause(&mut self) {
+        if !self.playing {
+            return;
+        }
+        self.playing = false;
+        self.send(Packet::Transport(Msg::Stop));
+    }
+
+    /// Holds the transport and rewinds it to the first frame.
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.snrkl.frame = 0;
+        self.send(Packet::Transport(Msg::Stop));
+    }
+
+    pub fn adjust_swing(&mut self, delta: i8) {
+        let swing = self.swing as i16 + delta as i16;
+        self.swing = swing.clamp(0, MAX_SWING as i16) as u8;
+    }
+
+    /// Sends one MIDI clock pulse.
+    pub fn pulse(&mut self) {
+        self.send(Packet::Transport(Msg::Clock));
+    }
+
+    fn send(&mut self, packet: Packet) {
+        if let Some(output) = &self.output {
+            if output.send(packet).is_err() {
+                log::error!("output thread is gone, disabling output");
+                self.output = None;
+            }
+        }
+    }
+
+    /// Applies a command sent by the patch through the `$` operator.
+    pub fn command(&mut self, cmd: Command) {
+        match cmd {
+            Command::Bpm(bpm) if bpm > 0 => self.bpm = bpm,
+            Command::Bpm(_) => log::warn!("ignoring bpm of 0"),
+            Command::Play => self.play(),
+            Command::Stop => self.pause(),
+            Command::Frame(frame) => self.snrkl.frame = frame,
+            Command::Write { text, x, y } => {
+                let mut loc = Coord { x, y };
+                for c in text.chars() {
+                    if let Some(op) = crate::op::Op::from(c, self.snrkl.frame) {
+                        let _ignored = self.snrkl.set_cell(&loc, op);
+                    }
+                    loc.x += 1;
+                }
+            }
+            Command::Load(file) => {
+                let mut path = PathBuf::from(file);
+                if path.extension().is_none() {
+                    path.set_extension("orca");
+                }
+                if let Err(err) = self.load(&path) {
+                    log::error!("could not load {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+
+    /// Runs a command typed on the `:` command line.
+    pub fn run(&mut self, cmd: EditorCommand) {
+        match cmd {
+            EditorCommand::Write(path) => match path.or_else(|| self.file.clone()) {
+                Some(path) => {
+                    if let Err(err) = self.save(&path) {
+                        log::error!("could not save {}: {}", path.display(), err);
+                    }
+                }
+                None => log::warn!("no file to save to"),
+            },
+            EditorCommand::Edit(path) => {
+                if let Err(err) = self.load(&path) {
+                    log::error!("could not load {}: {}", path.display(), err);
+                }
+            }
+            EditorCommand::Quit => self.edit_state = EditorState::QuitConfirmed,
+            EditorCommand::Resize { cols, rows } => {
+                self.fit_terminal = false;
+                self.resize(cols, rows);
+            }
+            EditorCommand::Bpm(bpm) => self.command(Command::Bpm(bpm)),
+            EditorCommand::Grid { x, y } => match (u8::try_from(x), u8::try_from(y)) {
+                (Ok(x @ 1..), Ok(y @ 1..)) => {
+                    self.config.grid_steps_x = x;
+                    self.config.grid_steps_y = y;
+                }
+                _ => log::warn!("grid steps must be between 1 and 255"),
+            },
+            EditorCommand::Goto(loc) => {
+                self.cursor.x = cmp::min(loc.x, self.snrkl.cols - 1);
+                self.cursor.y = cmp::min(loc.y, self.snrkl.rows - 1);
+            }
+        }
+    }
+
+    /// Crops or pads the grid to `cols` x `rows`, keeping the cursor and
+    /// the selection on it. Cropped cells come back on undo.
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        if cols == 0 || rows == 0 {
+            log::warn!("ignoring empty grid size {}x{}", cols, rows);
+            return;
+        }
+        if (cols, rows) == (self.snrkl.cols, self.snrkl.rows) {
+            return;
+        }
+        let undo = self.snrkl.resize(cols, rows);
+        self.undo_steps.push(undo);
+        self.clamp_to_grid();
+    }
+
+    /// Grows the grid to at least `cols` x `rows`. It never shrinks, and as
+    /// nothing is lost it is not recorded for undo.
+    pub fn pad(&mut self, cols: usize, rows: usize) {
+        let cols = cmp::max(cols, self.snrkl.cols);
+        let rows = cmp::max(rows, self.snrkl.rows);
+        if (cols, rows) != (self.snrkl.cols, self.snrkl.rows) {
+            self.snrkl.resize(cols, rows);
+        }
+    }
+
+    /// Applies an undo or redo step, returning the step that reverts it.
+    fn revert(&mut self, step: UndoOp) -> UndoOp {
+        match step {
+            UndoOp::Step { ts: _, loc, op } => {
+                let old = self.restore(vec![(loc, op)]).remove(0);
+                UndoOp::step(&old.0, old.1)
+            }
+            UndoOp::Batch { ts: _, ops } => UndoOp::batch(self.restore(ops)),
+            UndoOp::Resize {
+                ts: _,
+                cols,
+                rows,
+                ops,
+            } => {
+                let undo = self.snrkl.resize(cols, rows);
+                self.restore(ops);
+                self.clamp_to_grid();
+                undo
+            }
+        }
+    }
+
+    /// Sets or clears cells, returning what they held before.
+    fn restore(&mut self, ops: Vec<(Coord, Option<Op>)>) -> Vec<(Coord, Option<Op>)> {
+        let mut old_ops = vec![];
+        for (loc, op) in ops {
+            let old = match op {
+                Some(op) => self.snrkl.set_cell(&loc, op),
+                None => self.snrkl.del_cell(&loc),
+            };
+            old_ops.push((loc, old));
+        }
+        old_ops
+    }
+
+    /// Moves the cursor and the selection back onto the grid.
+    fn clamp_to_grid(&mut self) {
+        let max_x = self.snrkl.cols - 1;
+        let max_y = self.snrkl.rows - 1;
+        self.cursor.x = cmp::min(self.cursor.x, max_x);
+        self.cursor.y = cmp::min(self.cursor.y, max_y);
+        if let Some(sel) = &mut self.sel_start {
+            sel.x = cmp::min(sel.x, max_x);
+            sel.y = cmp::min(sel.y, max_y);
+        }
+    }
+
+    /// Replaces the grid with the contents of an `.orca` file, which also
+    /// becomes the file the grid is saved to.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let text = fs::read_to_string(&path)?;
+        let snrkl = Snorkel::from_orca(&text, self.snrkl.frame);
+        if snrkl.rows == 0 || snrkl.cols == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty grid"));
+        }
+        self.snrkl = snrkl;
+        self.cursor.x = cmp::min(self.cursor.x, self.snrkl.cols - 1);
+        self.cursor.y = cmp::min(self.cursor.y, self.snrkl.rows - 1);
+        self.sel_start = None;
+        self.undo_steps.clear();
+        self.redo_steps.clear();
+        self.file = Some(path.as_ref().to_path_buf());
+        log::info!("loaded {}", path.as_ref().display());
+        Ok(())
+    }
+
+    /// Writes the grid to `path` in the `.orca` format.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        fs::write(&path, self.snrkl.to_orca())?;
+        self.file = Some(path.as_ref().to_path_buf());
+        log::info!("saved {}", path.as_ref().display());
+        Ok(())
     }
 
     pub fn move_cursor(&mut self, mov: Movement) {
@@ -263,6 +598,336 @@ impl AppState {
     }
 }
 
+#[cfg(test)]
+mod commands {
+    use crate::{command::Command, op::Op, state::AppState, util::Coord};
+
+    #[test]
+    fn should_apply_transport_commands() {
+        let mut app = AppState::new(8, 8);
+        app.command(Command::Bpm(140));
+        assert_eq!(140, app.bpm);
+        app.command(Command::Bpm(0));
+        assert_eq!(140, app.bpm);
+        app.command(Command::Play);
+        assert!(app.playing);
+        app.command(Command::Stop);
+        assert!(!app.playing);
+        app.command(Command::Frame(7));
+        assert_eq!(7, app.snrkl.frame);
+    }
+
+    #[test]
+    fn should_write_text_into_grid() {
+        let mut app = AppState::new(8, 8);
+        app.command(Command::Write {
+            text: "a1".to_string(),
+            x: 3,
+            y: 2,
+        });
+        assert_eq!(
+            Some(Op::Val('a')),
+            app.snrkl.get_cell(&Coord { x: 3, y: 2 })
+        );
+        assert_eq!(
+            Some(Op::Val('1')),
+            app.snrkl.get_cell(&Coord { x: 4, y: 2 })
+        );
+    }
+
+    #[test]
+    fn commands_from_grid_should_be_applied_on_tick() {
+        let mut app = AppState::new(4, 12);
+        app.snrkl.set_cell(&Coord { x: 0, y: 0 }, Op::Bang(0));
+        app.snrkl.set_cell(&Coord { x: 1, y: 0 }, Op::Command);
+        for (x, c) in "frame:9".chars().enumerate() {
+            let op = Op::from(c, 0).unwrap();
+            app.snrkl.set_cell(&Coord { x: x + 2, y: 0 }, op);
+        }
+        app.tick();
+        assert_eq!(9, app.snrkl.frame);
+    }
+}
+
+#[cfg(test)]
+mod transport {
+    use crate::{msg::Msg, output::Packet, state::AppState};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::sync::mpsc;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn should_play_pause_and_stop() {
+        let (tx, rx) = mpsc::channel();
+        let mut app = AppState::new(3, 3);
+        app.output = Some(tx);
+
+        app.input(key(' '));
+        assert!(app.playing);
+        assert_eq!(Packet::Transport(Msg::Start), rx.try_recv().unwrap());
+
+        app.next_frame();
+        app.next_frame();
+        rx.try_iter().for_each(drop);
+        app.input(key(' '));
+        assert!(!app.playing);
+        assert_eq!(2, app.snrkl.frame);
+        assert_eq!(Packet::Transport(Msg::Stop), rx.try_recv().unwrap());
+
+        app.input(key(' '));
+        assert!(app.playing);
+        assert_eq!(Packet::Transport(Msg::Continue), rx.try_recv().unwrap());
+
+        app.input(key('s'));
+        assert!(!app.playing);
+        assert_eq!(0, app.snrkl.frame);
+        assert_eq!(Packet::Transport(Msg::Stop), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn should_adjust_swing() {
+        let mut app = AppState::new(3, 3);
+        app.input(key('>'));
+        app.input(key('>'));
+        assert_eq!(10, app.swing);
+        app.input(key('<'));
+        assert_eq!(5, app.swing);
+        for _ in 0..4 {
+            app.input(key('<'));
+        }
+        assert_eq!(0, app.swing);
+        for _ in 0..20 {
+            app.input(key('>'));
+        }
+        assert_eq!(75, app.swing);
+    }
+}
+
+#[cfg(test)]
+mod cmdline {
+    use crate::{
+        state::{AppState, EditorState},
+        util::Coord,
+    };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn type_line(app: &mut AppState, line: &str) {
+        app.input(KeyEvent::new(KeyCode::Char(':'), KeyModifiers::NONE));
+        for c in line.chars() {
+            app.input(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        app.input(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn should_run_typed_commands() {
+        let mut app = AppState::new(8, 8);
+        type_line(&mut app, "bpm 90");
+        assert_eq!(EditorState::Normal, app.edit_state);
+        assert_eq!(90, app.bpm);
+
+        type_line(&mut app, "goto 5,20");
+        assert_eq!(Coord { x: 5, y: 7 }, app.cursor);
+
+        type_line(&mut app, "grid 4 2");
+        assert_eq!((4, 2), (app.config.grid_steps_x, app.config.grid_steps_y));
+        type_line(&mut app, "grid 0 2");
+        assert_eq!(4, app.config.grid_steps_x);
+
+        type_line(&mut app, "resize 4 3");
+        assert_eq!((4, 3), (app.snrkl.cols, app.snrkl.rows));
+        assert_eq!(Coord { x: 3, y: 2 }, app.cursor);
+
+        type_line(&mut app, "q");
+        assert_eq!(EditorState::QuitConfirmed, app.edit_state);
+    }
+
+    #[test]
+    fn should_recall_and_cancel_lines() {
+        let mut app = AppState::new(8, 8);
+        type_line(&mut app, "bpm 90");
+        app.input(KeyEvent::new(KeyCode::Char(':'), KeyModifiers::NONE));
+        app.input(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!("bpm 90", app.cmdline.input);
+        app.input(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(EditorState::Normal, app.edit_state);
+        assert_eq!("", app.cmdline.input);
+
+        app.input(KeyEvent::new(KeyCode::Char(':'), KeyModifiers::NONE));
+        app.input(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(EditorState::Normal, app.edit_state);
+    }
+}
+
+#[cfg(test)]
+mod resize {
+    use crate::{op::Op, state::AppState, util::Coord};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn should_keep_cursor_and_selection_on_the_grid() {
+        let mut app = AppState::new(8, 8);
+        app.cursor = Coord { x: 7, y: 6 };
+        app.sel_start = Some(Coord { x: 2, y: 7 });
+        app.resize(4, 3);
+        assert_eq!(Coord { x: 3, y: 2 }, app.cursor);
+        assert_eq!(Some(Coord { x: 2, y: 2 }), app.sel_start);
+    }
+
+    #[test]
+    fn should_undo_and_redo_resize() {
+        let mut app = AppState::new(4, 4);
+        app.snrkl.set_cell(&Coord { x: 3, y: 3 }, Op::Add);
+        app.snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Clock);
+        app.cursor = Coord { x: 3, y: 3 };
+        app.resize(2, 2);
+        assert_eq!(None, app.snrkl.get_cell(&Coord { x: 3, y: 3 }));
+        assert_eq!(Coord { x: 1, y: 1 }, app.cursor);
+
+        app.input(key('u'));
+        assert_eq!((4, 4), (app.snrkl.cols, app.snrkl.rows));
+        assert_eq!(Some(Op::Add), app.snrkl.get_cell(&Coord { x: 3, y: 3 }));
+        assert_eq!(Some(Op::Clock), app.snrkl.get_cell(&Coord { x: 1, y: 1 }));
+
+        app.input(KeyEvent::new(KeyCode::Char('U'), KeyModifiers::SHIFT));
+        assert_eq!((2, 2), (app.snrkl.cols, app.snrkl.rows));
+        app.input(key('u'));
+        assert_eq!(Some(Op::Add), app.snrkl.get_cell(&Coord { x: 3, y: 3 }));
+    }
+
+    #[test]
+    fn padding_should_only_grow_the_grid() {
+        let mut app = AppState::new(4, 4);
+        app.snrkl.set_cell(&Coord { x: 3, y: 3 }, Op::Add);
+        app.pad(2, 6);
+        assert_eq!((4, 6), (app.snrkl.cols, app.snrkl.rows));
+        app.pad(8, 2);
+        assert_eq!((8, 6), (app.snrkl.cols, app.snrkl.rows));
+        assert_eq!(Some(Op::Add), app.snrkl.get_cell(&Coord { x: 3, y: 3 }));
+        assert!(app.undo_steps.is_empty());
+    }
+
+    #[test]
+    fn should_ignore_empty_sizes() {
+        let mut app = AppState::new(4, 4);
+        app.resize(0, 4);
+        app.resize(4, 4);
+        assert_eq!((4, 4), (app.snrkl.cols, app.snrkl.rows));
+        assert!(app.undo_steps.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod devices {
+    use crate::{
+        output::{MidiDevices, Packet},
+        state::{AppState, EditorState},
+    };
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::sync::mpsc;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn should_pick_a_device() {
+        let (tx, rx) = mpsc::channel();
+        let mut app = AppState::new(3, 3);
+        app.output = Some(tx);
+        *app.midi_devices.lock().unwrap() = MidiDevices {
+            outputs: vec!["Midi Through".into(), "Digitakt".into(), "Synth".into()],
+            current: Some("Digitakt".into()),
+        };
+
+        app.input(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        assert_eq!(EditorState::PickDevice, app.edit_state);
+        assert_eq!(1, app.device_cursor);
+        assert_eq!(Packet::Rescan, rx.try_recv().unwrap());
+
+        for _ in 0..3 {
+            app.input(key(KeyCode::Down));
+        }
+        assert_eq!(2, app.device_cursor);
+        app.input(key(KeyCode::Enter));
+        assert_eq!(EditorState::Normal, app.edit_state);
+        assert_eq!(Packet::SelectDevice("Synth".into()), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn should_leave_the_picker_without_devices() {
+        let (tx, rx) = mpsc::channel();
+        let mut app = AppState::new(3, 3);
+        app.output = Some(tx);
+        app.pick_device();
+        app.input(key(KeyCode::Up));
+        app.input(key(KeyCode::Down));
+        assert_eq!(0, app.device_cursor);
+        app.input(key(KeyCode::Enter));
+        assert_eq!(EditorState::Normal, app.edit_state);
+        assert_eq!(vec![Packet::Rescan], rx.try_iter().collect::<Vec<_>>());
+    }
+}
+
+#[cfg(test)]
+mod files {
+    use crate::{op::Op, state::AppState, util::Coord};
+    use std::{fs, path::PathBuf};
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("snorkel-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn should_save_and_load_orca_file() {
+        let path = temp_file("save.orca");
+        let mut app = AppState::new(3, 5);
+        app.snrkl.set_cell(&Coord { x: 1, y: 1 }, Op::Add);
+        app.save(&path).unwrap();
+        assert_eq!(".....\n.A...\n.....\n", fs::read_to_string(&path).unwrap());
+
+        let mut other = AppState::new(20, 20);
+        other.cursor = Coord { x: 10, y: 10 };
+        other.load(&path).unwrap();
+        assert_eq!(3, other.snrkl.rows);
+        assert_eq!(5, other.snrkl.cols);
+        assert_eq!(Coord { x: 4, y: 2 }, other.cursor);
+        assert_eq!(Some(path.clone()), other.file);
+        assert_eq!(Some(Op::Add), other.snrkl.get_cell(&Coord { x: 1, y: 1 }));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_refuse_empty_file() {
+        let path = temp_file("empty.orca");
+        fs::write(&path, "").unwrap();
+        let mut app = AppState::new(3, 5);
+        assert!(app.load(&path).is_err());
+        assert_eq!(3, app.snrkl.rows);
+        assert_eq!(None, app.file);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_command_should_add_orca_extension() {
+        let path = temp_file("command.orca");
+        fs::write(&path, "1A1\n").unwrap();
+        let mut app = AppState::new(3, 5);
+        let name = path.with_extension("").display().to_string();
+        app.command(crate::command::Command::Load(name));
+        assert_eq!(Some(path.clone()), app.file);
+        assert_eq!(3, app.snrkl.cols);
+        fs::remove_file(path).unwrap();
+    }
+}
+
 #[cfg(test)]
 mod move_cursor {
     use crate::{mode::Movement, state::AppState};
@@ -0,0 +1,326 @@
+use crate::{
+    clock::{Clock, Sync, Tick},
+    state::AppState,
+};
+use std::{
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Orca runs four frames per beat, i.e. one frame per 16th note.
+pub const FRAMES_PER_BEAT: u64 = 4;
+
+/// MIDI clock runs at 24 pulses per beat, which makes 6 per frame.
+pub const PULSES_PER_FRAME: u32 = 6;
+
+/// The most every other frame can be delayed, in percent of a frame.
+pub const MAX_SWING: u8 = 75;
+
+/// How long a single frame lasts at `bpm`.
+pub fn frame_duration(bpm: usize) -> Duration {
+    Duration::from_micros(60_000_000 / (bpm.max(1) as u64 * FRAMES_PER_BEAT))
+}
+
+/// How long to wait before `frame` at `bpm`, when every odd frame is
+/// delayed by `swing` percent. The even frames make up for it, so pairs of
+/// frames keep the tempo.
+pub fn frame_interval(bpm: usize, swing: u8, frame: usize) -> Duration {
+    let dur = frame_duration(bpm);
+    let shift = dur * swing.min(MAX_SWING) as u32 / 100;
+    if frame % 2 == 1 {
+        dur + shift
+    } else {
+        dur - shift
+    }
+}
+
+/// The pulse within a pair of frames at which the odd frame starts. Swing
+/// is rounded to whole pulses here, since MIDI clock itself stays straight.
+fn swing_pulse(swing: u8) -> u32 {
+    PULSES_PER_FRAME + (PULSES_PER_FRAME * swing.min(MAX_SWING) as u32 + 50) / 100
+}
+
+/// Where to start counting pulses so that `frame` comes next.
+fn phase(frame: usize) -> u32 {
+    if frame % 2 == 1 {
+        PULSES_PER_FRAME
+    } else {
+        0
+    }
+}
+
+/// Handle to the thread that advances the grid in real time.
+pub struct Transport {
+    stop: mpsc::Sender<crate::Event>,
+    handle: JoinHandle<()>,
+}
+
+impl Transport {
+    /// Starts ticking `state` on `clock` at the state's bpm. Frames only
+    /// advance while the state is `playing`, tempo changes apply from the
+    /// next frame. With MIDI clock enabled, or when following an external
+    /// clock, the clock ticks once per pulse and every sixth pulse starts a
+    /// frame. Swing delays every odd frame, see [`frame_interval`].
+    pub fn spawn(state: Arc<Mutex<AppState>>, clock: Box<dyn Clock>) -> Transport {
+        let (stop, rx) = mpsc::channel();
+        let handle = thread::spawn(move || run(state, clock, rx));
+        Transport { stop, handle }
+    }
+
+    /// Stops the thread and waits for it to finish the current frame.
+    pub fn stop(self) {
+        let _ = self.stop.send(crate::Event);
+        self.join();
+    }
+
+    /// Waits for the thread without stopping it.
+    pub fn join(self) {
+        if self.handle.join().is_err() {
+            log::error!("transport thread panicked");
+        }
+    }
+}
+
+fn run(state: Arc<Mutex<AppState>>, mut clock: Box<dyn Clock>, rx: mpsc::Receiver<crate::Event>) {
+    if clock.realtime() {
+        let rt = crate::linux::setup_rt_priority();
+        state.lock().unwrap().rt_priority = Some(rt);
+    }
+
+    // ░█░░░█▀█░█▀█░█▀█
+    // ░█░░░█░█░█░█░█▀▀
+    // ░▀▀▀░▀▀▀░▀▀▀░▀░░
+
+    // counts the pulses of a pair of frames
+    let mut pulse = phase(state.lock().unwrap().snrkl.frame + 1);
+    let mut last_tick: Option<Instant> = None;
+    loop {
+        let (bpm, swing, next, midi_clock) = {
+            let state = state.lock().unwrap();
+            let midi_clock = state.config.output.midi_clock;
+            (state.bpm, state.swing, state.snrkl.frame + 1, midi_clock)
+        };
+        let per_pulse = midi_clock || clock.pulses();
+        let interval = if per_pulse {
+            frame_duration(bpm) / PULSES_PER_FRAME
+        } else {
+            frame_interval(bpm, swing, next)
+        };
+        let tick = clock.wait(interval);
+        let now = Instant::now();
+        if tick == Tick::Done {
+            break;
+        }
+
+        if let Ok(_) | Err(TryRecvError::Disconnected) = rx.try_recv() {
+            break;
+        }
+
+        let mut state = state.lock().unwrap();
+        if tick == Tick::Now {
+            if let Some(last) = last_tick {
+                let elapsed = now.duration_since(last).as_micros() as i64;
+                state.jitter.record(elapsed - interval.as_micros() as i64);
+            }
+            last_tick = Some(now);
+        } else {
+            last_tick = None;
+        }
+        if let Some(bpm) = clock.bpm() {
+            state.bpm = bpm.round() as usize;
+            state.synced = true;
+        }
+        while let Some(sync) = clock.sync() {
+            match sync {
+                Sync::Start => {
+                    state.stop();
+                    state.play();
+                }
+                Sync::Stop => state.pause(),
+                Sync::Continue => state.play(),
+                Sync::Position(frame) => state.snrkl.frame = frame,
+            }
+            pulse = phase(state.snrkl.frame + 1);
+        }
+        if tick == Tick::Idle {
+            continue;
+        }
+        if !state.playing {
+            // resume on a frame boundary
+            pulse = phase(state.snrkl.frame + 1);
+            continue;
+        }
+        if midi_clock {
+            state.pulse();
+        }
+        if !per_pulse || pulse == 0 || pulse == swing_pulse(state.swing) {
+            state.next_frame();
+        }
+        pulse = (pulse + 1) % (2 * PULSES_PER_FRAME);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_duration, frame_interval, swing_pulse, Transport};
+    use crate::{
+        clock::{ClockMsg, MidiClock, Sync, VirtualClock},
+        command::Command,
+        msg::Msg,
+        op::Op,
+        output::Packet,
+        state::AppState,
+        util::Coord,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    fn transport(app: AppState, frames: usize) -> (AppState, Vec<Duration>) {
+        let (clock, handle) = VirtualClock::new();
+        let state = Arc::new(Mutex::new(app));
+        let transport = Transport::spawn(state.clone(), Box::new(clock));
+        handle.advance(frames);
+        let intervals = handle.intervals();
+        drop(handle);
+        transport.join();
+        let app = Arc::try_unwrap(state).ok().unwrap().into_inner().unwrap();
+        (app, intervals)
+    }
+
+    #[test]
+    fn frame_duration_should_be_a_16th_note() {
+        assert_eq!(Duration::from_millis(125), frame_duration(120));
+        assert_eq!(Duration::from_micros(166_666), frame_duration(90));
+    }
+
+    #[test]
+    fn swing_should_delay_odd_frames() {
+        assert_eq!(Duration::from_millis(125), frame_interval(120, 0, 1));
+        assert_eq!(Duration::from_millis(125), frame_interval(120, 0, 2));
+        assert_eq!(Duration::from_micros(156_250), frame_interval(120, 25, 1));
+        assert_eq!(Duration::from_micros(93_750), frame_interval(120, 25, 2));
+        assert_eq!(frame_interval(120, 75, 1), frame_interval(120, 100, 1));
+        assert_eq!(6, swing_pulse(0));
+        assert_eq!(8, swing_pulse(33));
+        assert_eq!(11, swing_pulse(100));
+    }
+
+    #[test]
+    fn should_swing_frames() {
+        let mut app = AppState::new(3, 3);
+        app.swing = 50;
+        app.playing = true;
+        let (app, intervals) = transport(app, 4);
+        assert_eq!(4, app.snrkl.frame);
+        assert_eq!(
+            vec![
+                Duration::from_micros(187_500),
+                Duration::from_micros(62_500),
+                Duration::from_micros(187_500),
+                Duration::from_micros(62_500)
+            ],
+            intervals[..4]
+        );
+    }
+
+    #[test]
+    fn should_swing_frames_on_clock_pulses() {
+        let frames_after = |pulses| {
+            let mut app = AppState::new(3, 3);
+            app.config.output.midi_clock = true;
+            app.swing = 50;
+            app.playing = true;
+            transport(app, pulses).0.snrkl.frame
+        };
+        // frame 1 is held back by 3 pulses, frame 2 comes right on time
+        assert_eq!(0, frames_after(3));
+        assert_eq!(1, frames_after(4));
+        assert_eq!(1, frames_after(6));
+        assert_eq!(2, frames_after(7));
+    }
+
+    #[test]
+    fn should_advance_frames_while_playing() {
+        let mut app = AppState::new(3, 3);
+        app.playing = true;
+        let (app, intervals) = transport(app, 5);
+        assert_eq!(5, app.snrkl.frame);
+        assert_eq!(vec![Duration::from_millis(125); 5], intervals[..5]);
+        // the virtual clock ticks right away, way ahead of time
+        assert_eq!(4, app.jitter.count());
+        assert!(app.jitter.max().unwrap() < 0);
+    }
+
+    #[test]
+    fn should_send_six_clock_pulses_per_frame() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut app = AppState::new(3, 3);
+        app.config.output.midi_clock = true;
+        app.output = Some(tx);
+        app.play();
+        let (app, intervals) = transport(app, 12);
+        assert_eq!(2, app.snrkl.frame);
+        assert_eq!(vec![Duration::from_millis(125) / 6; 12], intervals[..12]);
+        let pulses = rx
+            .try_iter()
+            .filter(|packet| *packet == Packet::Transport(Msg::Clock))
+            .count();
+        assert_eq!(12, pulses);
+    }
+
+    #[test]
+    fn should_follow_external_midi_clock() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let state = Arc::new(Mutex::new(AppState::new(3, 3)));
+        let transport = Transport::spawn(state.clone(), Box::new(MidiClock::new(rx)));
+
+        // two frames at 150 bpm, positioned at frame 8
+        let start = std::time::Instant::now();
+        let pulse = Duration::from_millis(400) / 24;
+        tx.send((ClockMsg::Sync(Sync::Position(8)), start)).unwrap();
+        tx.send((ClockMsg::Sync(Sync::Continue), start)).unwrap();
+        for i in 0..12 {
+            tx.send((ClockMsg::Pulse, start + pulse * i)).unwrap();
+        }
+        // clocks keep pulsing while stopped
+        tx.send((ClockMsg::Sync(Sync::Stop), start + pulse * 12))
+            .unwrap();
+        tx.send((ClockMsg::Pulse, start + pulse * 12)).unwrap();
+        drop(tx);
+        transport.join();
+
+        let app = state.lock().unwrap();
+        assert_eq!(10, app.snrkl.frame);
+        assert_eq!(150, app.bpm);
+        assert!(app.synced);
+        assert!(!app.playing);
+    }
+
+    #[test]
+    fn should_not_advance_frames_while_stopped() {
+        let (app, _) = transport(AppState::new(3, 3), 5);
+        assert_eq!(0, app.snrkl.frame);
+    }
+
+    #[test]
+    fn should_follow_tempo_changes() {
+        let mut app = AppState::new(2, 10);
+        app.playing = true;
+        app.command(Command::Write {
+            text: "$bpm:60".to_string(),
+            x: 0,
+            y: 0,
+        });
+        app.snrkl.set_cell(&Coord { x: 0, y: 1 }, Op::Bang(0));
+        let (app, intervals) = transport(app, 2);
+        assert_eq!(60, app.bpm);
+        assert_eq!(Duration::from_millis(125), intervals[0]);
+        assert_eq!(Duration::from_millis(250), intervals[1]);
+    }
+}
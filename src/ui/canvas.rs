@@ -2,6 +2,7 @@ use crate::{
     state::{AppState, EditorState},
     util::{Coord, Selection},
 };
+use std::cmp;
 use tui::{
     text::{Span, Spans},
     widgets::{Block, Borders, Paragraph},
@@ -29,16 +30,26 @@ pub fn render(state: &AppState) -> Paragraph {
     let grid_x = state.config.grid_steps_x as usize;
     let grid_y = state.config.grid_steps_y as usize;
 
+    let view = &state.viewport;
+    let end_x = cmp::min(view.x + view.cols, state.snrkl.cols);
+    let end_y = cmp::min(view.y + view.rows, state.snrkl.rows);
+
     let mut text = vec![];
-    // go through all rows
-    for y in 0..state.snrkl.rows {
+    // go through all rows in view
+    for y in view.y..end_y {
         let mut spn = vec![];
         let mut frag = String::new();
-        let mut in_comment = false;
+        // comments opened left of the view carry on into it
+        let mut in_comment = (0..view.x)
+            .filter_map(|x| state.snrkl.get_cell(&Coord { x, y }))
+            .filter(|op| op.is_comment())
+            .count()
+            % 2
+            == 1;
         let mut in_selection = false;
 
-        // go through all cells in current row
-        for x in 0..state.snrkl.cols {
+        // go through all cells of the current row in view
+        for x in view.x..end_x {
             let point = Coord { x, y };
             let is_cursor = state.cursor == point;
 
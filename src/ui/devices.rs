@@ -0,0 +1,46 @@
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use crate::state::AppState;
+
+pub fn render<B: Backend>(f: &mut Frame<B>, area: Rect, state: &AppState) {
+    let devices = state.midi_devices.lock().unwrap();
+    let items: Vec<ListItem> = devices
+        .outputs
+        .iter()
+        .map(|name| {
+            if devices.current.as_ref() == Some(name) {
+                ListItem::new(format!("* {}", name))
+            } else {
+                ListItem::new(format!("  {}", name))
+            }
+        })
+        .collect();
+    let title = if items.is_empty() {
+        "MIDI output | no devices found"
+    } else {
+        "MIDI output | enter to select"
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(title)
+                .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White).bg(Color::Black))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut list_state = ListState::default();
+    if !devices.outputs.is_empty() {
+        list_state.select(Some(state.device_cursor));
+    }
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut list_state);
+}
@@ -0,0 +1,47 @@
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{BarChart, Block, Borders},
+    Frame,
+};
+
+use crate::state::AppState;
+
+pub const HEIGHT: u16 = 12;
+
+pub fn render<B: Backend>(f: &mut Frame<B>, area: Rect, state: &AppState) {
+    let jitter = &state.jitter;
+    let title = match (jitter.min(), jitter.max(), jitter.mean()) {
+        (Some(min), Some(max), Some(mean)) => format!(
+            "Jitter | {} ticks | min {}us | max {}us | mean {:.1}us",
+            jitter.count(),
+            min,
+            max,
+            mean
+        ),
+        _ => "Jitter | no ticks yet".to_string(),
+    };
+
+    let bins = jitter.bins();
+    let data: Vec<(&str, u64)> = bins
+        .iter()
+        .map(|(label, count)| (label.as_str(), *count))
+        .collect();
+
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .title(title)
+                .border_style(Style::default().fg(Color::White).bg(Color::Black))
+                .borders(Borders::ALL),
+        )
+        .data(&data)
+        .bar_width(6)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .label_style(Style::default().fg(Color::White));
+
+    f.render_widget(chart, area);
+}
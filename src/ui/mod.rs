@@ -1,12 +1,15 @@
 use tui::{
+    buffer::Buffer,
     layout::{self, Constraint, Direction, Layout, Rect},
-    widgets::{Block, Borders, Clear},
+    widgets::{Block, Borders, Clear, Widget},
 };
 
 use crate::state::{self, EditorState};
 
 mod bottombar;
 mod canvas;
+mod devices;
+mod jitter;
 mod topbar;
 
 fn full_layout(size: Rect) -> Vec<Rect> {
@@ -36,24 +39,49 @@ fn minimal_layout(size: Rect) -> Vec<Rect> {
         .split(size)
 }
 
-pub fn render<B: tui::backend::Backend>(f: &mut tui::Frame<B>, state: &state::AppState) {
+pub fn render<B: tui::backend::Backend>(f: &mut tui::Frame<B>, state: &mut state::AppState) {
     let chunks = if state.show_logs {
         full_layout(f.size())
     } else {
         minimal_layout(f.size())
     };
 
+    let canvas_area = if state.show_jitter {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(jitter::HEIGHT)].as_ref())
+            .split(chunks[1]);
+        jitter::render(f, split[1], state);
+        split[0]
+    } else {
+        chunks[1]
+    };
+
+    let cursor = state.cursor.clone();
+    let (cols, rows) = (state.snrkl.cols, state.snrkl.rows);
+    state.viewport.follow(
+        &cursor,
+        canvas_area.width as usize,
+        canvas_area.height as usize,
+        cols,
+        rows,
+    );
+
     let p = topbar::render(&state);
     f.render_widget(p, chunks[0]);
 
     let p = canvas::render(&state);
-    f.render_widget(p, chunks[1]);
+    f.render_widget(p, canvas_area);
 
     if state.show_logs {
         let p = bottombar::render(&state);
         f.render_widget(p, chunks[2]);
     }
 
+    if state.edit_state == EditorState::PickDevice {
+        devices::render(f, centered_rect(60, 40, f.size()), state);
+    }
+
     if state.edit_state == EditorState::QuitRequested {
         let block = Block::default()
             .title("Quit Snorkel?")
@@ -64,6 +92,16 @@ pub fn render<B: tui::backend::Backend>(f: &mut tui::Frame<B>, state: &state::Ap
     }
 }
 
+/// A frame rendered earlier, e.g. into an off-screen terminal while the
+/// state was locked, to be drawn once the lock is released.
+pub struct Snapshot<'a>(pub &'a Buffer);
+
+impl Widget for Snapshot<'_> {
+    fn render(self, _area: Rect, buf: &mut Buffer) {
+        buf.merge(self.0);
+    }
+}
+
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
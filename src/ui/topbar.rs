@@ -24,6 +24,14 @@ pub fn render(state: &AppState) -> Table {
             "select",
             Style::default().bg(Color::Yellow).fg(Color::Black),
         ),
+        EditorState::Command => Span::styled(
+            "command",
+            Style::default().bg(Color::LightCyan).fg(Color::Black),
+        ),
+        EditorState::PickDevice => Span::styled(
+            "device",
+            Style::default().bg(Color::LightMagenta).fg(Color::Black),
+        ),
         EditorState::QuitRequested => Span::styled(
             "quitting",
             Style::default().bg(Color::Yellow).fg(Color::Black),
@@ -36,21 +44,52 @@ pub fn render(state: &AppState) -> Table {
     let grid = format!("{}x{}", state.snrkl.cols, state.snrkl.rows);
     let pos = format!("{},{}", state.cursor.x, state.cursor.y);
     let frame = format!("{}f", state.snrkl.frame);
+    let bpm = if state.synced {
+        format!("{}bpm ext", state.bpm)
+    } else {
+        format!("{}bpm", state.bpm)
+    };
+    let priority = match &state.rt_priority {
+        Some(rt) if !rt.is_realtime() => Span::styled(
+            "no rt-priority",
+            Style::default().bg(Color::Red).fg(Color::Black),
+        ),
+        _ => Span::raw(""),
+    };
+    let swing = format!("{}% swing", state.swing);
+    let scroll = if state.viewport.clips(state.snrkl.cols, state.snrkl.rows) {
+        format!("view {},{}", state.viewport.x, state.viewport.y)
+    } else {
+        String::new()
+    };
+    let cmdline = if state.edit_state == EditorState::Command {
+        Span::styled(
+            format!(":{}\u{2588}", state.cmdline.input),
+            Style::default().fg(Color::LightCyan),
+        )
+    } else {
+        Span::raw("")
+    };
+    let transport = match (state.playing, state.snrkl.frame) {
+        (true, _) => Span::styled("play", Style::default().fg(Color::LightGreen)),
+        (false, 0) => Span::styled("stop", Style::default().fg(Color::Red)),
+        (false, _) => Span::styled("pause", Style::default().fg(Color::Yellow)),
+    };
 
     let rows = vec![
         Row::new(vec![
             Cell::from(grid),
             Cell::from(frame),
-            Cell::from(""),
-            Cell::from(""),
-            Cell::from(""),
+            Cell::from(transport),
+            Cell::from(priority),
+            Cell::from(scroll),
         ]),
         Row::new(vec![
             Cell::from(pos),
             Cell::from(editor_state),
-            Cell::from(""),
-            Cell::from(""),
-            Cell::from(""),
+            Cell::from(bpm),
+            Cell::from(swing),
+            Cell::from(cmdline),
         ]),
     ];
 
@@ -59,6 +98,8 @@ pub fn render(state: &AppState) -> Table {
         .widths(&[
             Constraint::Percentage(10),
             Constraint::Length(20),
-            Constraint::Min(10),
+            Constraint::Length(12),
+            Constraint::Length(16),
+            Constraint::Min(20),
         ])
 }